@@ -1,10 +1,21 @@
-use cosmwasm_std::{to_binary, Api, Binary, Env, Extern, HandleResponse, HandleResult, InitResponse, Querier, StdError, StdResult, Storage, Uint128, HumanAddr, CosmosMsg, BankMsg, Coin, WasmMsg};
+use cosmwasm_std::{log, to_binary, from_binary, Api, Binary, Env, Extern, HandleResponse, HandleResult, InitResponse, Querier, StdError, StdResult, Storage, Uint128, HumanAddr, CosmosMsg, BankMsg, Coin, WasmMsg, Decimal};
+use secret_toolkit::utils::{pad_handle_result, pad_query_result};
+use secret_toolkit::viewing_key::{ViewingKey, ViewingKeyStore};
 use crate:: {
-    msg::{HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg},
-    state:: {store_owner, read_owner},
+    msg::{HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, ReceiveMsg, ResponseStatus, Circuit, CircuitLimits, ContractStatus, FeeLedger, GameInfo, HeldPayout, LedgerAction, LedgerEntry, LossBucket, LossWindow, PendingWithdrawal, ReferralConfig, RoundRecord, Settlement, Stake, Stats, StatsView, TokenPool, DEFAULT_BLOCK_SIZE, MAX_BLOCK_SIZE, DEFAULT_DENOM, DEFAULT_FEE_BPS, MAX_FEE_BPS, MAX_LEDGER_PAGE, MAX_PAYOUT_BPS, MAX_STATS_DAYS, DEFAULT_EMERGENCY_DELAY, MIN_EMERGENCY_DELAY},
+    state:: {store_owner, read_owner, store_proposed_owner, read_proposed_owner, store_operator, read_operator},
 };
-use crate::state::{read_game_address, store_game_address, read_bank_address, store_bank_address, set_pending_game_address, is_pending_game_address, unset_pending_game_address};
-mod game_msg;
+use crate::contract::game_msg::{TableConfig, Token};
+use crate::state::{store_token_list, read_token_list, store_token_pool, read_token_pool, store_pending_game, read_pending_game, store_secret, read_secret, store_games_created, read_games_created, store_game_list, read_game_list, store_game, read_game, remove_game, read_bank_address, store_bank_address, set_pending_game_address, is_pending_game_address, unset_pending_game_address, store_block_size, read_block_size,
+                   store_pool, read_pool, store_lp_shares, read_lp_shares, store_fee_bps, read_fee_bps, store_fees, read_fees, append_ledger_entry, read_ledger,
+                   store_status, read_status, store_circuit, read_circuit, store_circuit_limits, read_circuit_limits, store_loss_window, read_loss_window,
+                   store_escrow, read_escrow, remove_escrow, store_round_record, read_round_record, store_emergency_delay, read_emergency_delay, store_emergency_withdrawal, read_emergency_withdrawal,
+                   store_stats, read_stats, store_referral_config, read_referral_config, store_referrer, read_referrer, store_referral_rewards, read_referral_rewards};
+pub mod game_msg;
+mod snip20_msg;
+
+const SECONDS_PER_HOUR: u64 = 60 * 60;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -12,167 +23,2748 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
     store_owner(&mut deps.storage, &env.message.sender)?;
+    store_proposed_owner(&mut deps.storage, &None)?;
+    store_operator(&mut deps.storage, &env.message.sender)?;
     store_bank_address(&mut deps.storage, &env.contract.address)?;
+    let block_size = msg.block_size.unwrap_or(DEFAULT_BLOCK_SIZE);
+    check_block_size(block_size)?;
+    store_block_size(&mut deps.storage, &block_size)?;
+    store_game_list(&mut deps.storage, &[])?;
+    store_token_list(&mut deps.storage, &[])?;
+    store_secret(&mut deps.storage, &msg.secret)?;
+    store_games_created(&mut deps.storage, &0)?;
+    store_circuit(&mut deps.storage, &Circuit::default())?;
+    store_status(&mut deps.storage, &ContractStatus::Normal)?;
+    store_emergency_withdrawal(&mut deps.storage, &None)?;
+    store_referral_config(&mut deps.storage, &ReferralConfig::default())?;
 
-    let game_contract_label = "SJ-Game".to_string() + env.contract.address.as_str();
-    let mut messages = vec![];
-    messages.extend(vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
-        code_id: u64::from(msg.game_contract_code_id),
-        callback_code_hash: msg.game_contract_code_hash.to_lowercase(),
+    let emergency_delay = msg.emergency_delay.unwrap_or(DEFAULT_EMERGENCY_DELAY);
+    check_emergency_delay(emergency_delay)?;
+    store_emergency_delay(&mut deps.storage, &emergency_delay)?;
+
+    let fee_bps = msg.fee_bps.unwrap_or(DEFAULT_FEE_BPS);
+    check_fee_bps(fee_bps)?;
+    store_fee_bps(&mut deps.storage, &fee_bps)?;
+
+    let mut viewing_key_seed : Vec<u8> = msg.secret.to_be_bytes().to_vec();
+    viewing_key_seed.extend(env.contract.address.as_str().as_bytes());
+    ViewingKey::set_seed(&mut deps.storage, &viewing_key_seed);
+
+    let messages = vec![instantiate_game(
+        deps,
+        &env,
+        msg.game_contract_code_id,
+        msg.game_contract_code_hash,
+        msg.game_config.unwrap_or_default(),
+        None,
+    )?];
+
+    Ok(InitResponse {
+        messages,
+        log: vec![],
+    })
+}
+
+/// Instantiates a game table that registers itself back through `UpdateGameAddress`, in the same transaction
+fn instantiate_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    code_id: u64,
+    code_hash: String,
+    rules: TableConfig,
+    exposure_limit: Option<Uint128>,
+) -> StdResult<CosmosMsg> {
+    check_block_size(rules.block_size)?;
+
+    let games_created = read_games_created(&deps.storage)?;
+    store_games_created(&mut deps.storage, &(games_created + 1))?;
+
+    // The first table keeps the original label, later ones are numbered
+    let mut game_contract_label = "SJ-Game".to_string() + env.contract.address.as_str();
+    if games_created > 0 {
+        game_contract_label = format!("{}-{}", game_contract_label, games_created);
+    }
+
+    let message = CosmosMsg::Wasm(WasmMsg::Instantiate {
+        code_id,
+        callback_code_hash: code_hash.to_lowercase(),
         msg: to_binary(&game_msg::InitMsg {
             bank_address: env.contract.address.clone(),
             bank_code_hash: env.contract_code_hash.as_str().to_string(),
-            secret: msg.secret,
+            secret: read_secret(&deps.storage)?.wrapping_add(u64::from(games_created)),
+            config: Some(rules.clone()),
         })?,
         send: vec![],
         label: game_contract_label,
-    })]);
+    });
 
+    store_pending_game(&mut deps.storage, &GameInfo::new(HumanAddr::default(), Some(rules), exposure_limit))?;
     set_pending_game_address(&mut deps.storage)?;
-    Ok(InitResponse {
-        messages,
+
+    Ok(message)
+}
+
+pub fn create_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    code_id: u64,
+    code_hash: String,
+    rules: TableConfig,
+    exposure_limit: Option<Uint128>,
+) -> HandleResult {
+    let message = instantiate_game(deps, &env, code_id, code_hash, rules, exposure_limit)?;
+
+    Ok(HandleResponse {
+        messages: vec![message],
         log: vec![],
+        data: Some(to_binary(&HandleAnswer::UpdateGame { status: ResponseStatus::Success })?),
     })
 }
 
-pub fn change_owner<S: Storage, A: Api, Q: Querier>(
+/// Ownership only moves once the proposed owner accepts it, so a typo in the address can be cancelled
+pub fn propose_owner<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    _env: &Env,
     new_owner: HumanAddr,
 ) -> HandleResult {
-    store_owner(&mut deps.storage, &new_owner)?;
+    store_proposed_owner(&mut deps.storage, &Some(new_owner))?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: Some(to_binary(&HandleAnswer::ChangeOwner {})?),
+        data: Some(to_binary(&HandleAnswer::ProposeOwner {})?),
     })
 }
 
-pub fn withdraw<S: Storage, A: Api, Q: Querier>(
+pub fn accept_ownership<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    amount: Uint128,
-    send_to: HumanAddr,
-    all: bool,
 ) -> HandleResult {
-    let to: HumanAddr;
+    match read_proposed_owner(&deps.storage)? {
+        Some(proposed_owner) if proposed_owner == env.message.sender => {}
+        _ => return Err(StdError::unauthorized()),
+    }
 
-    if all {
-        check_owner(deps, &env)?;
-        to = read_owner(&deps.storage)?;
-    } else {
-        if read_game_address(&deps.storage)? != env.message.sender {
-            return Err(StdError::generic_err("Only game contract can withdraw funds".to_string()));
-        }
+    store_owner(&mut deps.storage, &env.message.sender)?;
+    store_proposed_owner(&mut deps.storage, &None)?;
 
-        to = send_to;
-    }
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::AcceptOwnership {})?),
+    })
+}
 
-    let curr_balance = deps.querier.query_balance(read_bank_address(&deps.storage)?, "uscrt")?.amount;
-    if amount > curr_balance {
-        return Err(StdError::generic_err(format!(
-            "Insufficient bank balance, asked for: {}, balance is: {}",
-            amount,
-            curr_balance
-        )));
+pub fn cancel_ownership_proposal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+) -> HandleResult {
+    if read_proposed_owner(&deps.storage)?.is_none() {
+        return Err(StdError::generic_err("There is no pending ownership proposal"));
     }
 
+    store_proposed_owner(&mut deps.storage, &None)?;
+
     Ok(HandleResponse {
-        messages: vec![CosmosMsg::Bank(BankMsg::Send {
-            from_address: env.contract.address,
-            to_address: to,
-            amount: vec![Coin::new(amount.u128(), "uscrt")],
-        })],
+        messages: vec![],
         log: vec![],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::CancelOwnershipProposal {})?),
     })
 }
 
-pub fn update_game_address<S: Storage, A: Api, Q: Querier>(
+pub fn set_operator<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    address: HumanAddr,
+    operator: HumanAddr,
 ) -> HandleResult {
-    store_game_address(&mut deps.storage, &address)?;
-    unset_pending_game_address(&mut deps.storage)?;
+    store_operator(&mut deps.storage, &operator)?;
 
     Ok(HandleResponse {
         messages: vec![],
         log: vec![],
-        data: None,
+        data: Some(to_binary(&HandleAnswer::SetOperator {})?),
     })
 }
 
+/// Ledger entry of a movement at the current block, fill in the game and round where there is one
+fn ledger_entry(env: &Env, action: LedgerAction, account: HumanAddr, denom: &str, amount: Uint128) -> LedgerEntry {
+    LedgerEntry {
+        id: 0,
+        action,
+        game: None,
+        account,
+        round: None,
+        denom: denom.to_string(),
+        amount,
+        time: env.block.time,
+    }
+}
+
+/// Drops the buckets that fell out of the window and returns the bucket of the current hour
+fn loss_bucket(window: &mut LossWindow, now: u64, length: u64) -> &mut LossBucket {
+    let oldest = now.saturating_sub(length) / SECONDS_PER_HOUR;
+    window.buckets.retain(|bucket| bucket.hour >= oldest);
 
-pub fn handle<S: Storage, A: Api, Q: Querier>(
+    let hour = now / SECONDS_PER_HOUR;
+    if window.buckets.last().map_or(true, |bucket| bucket.hour != hour) {
+        window.buckets.push(LossBucket { hour, won: Uint128::from(0_u128), paid: Uint128::from(0_u128) });
+    }
+    window.buckets.last_mut().unwrap()
+}
+
+fn net_loss(window: &LossWindow) -> u128 {
+    let won: u128 = window.buckets.iter().map(|bucket| bucket.won.u128()).sum();
+    let paid: u128 = window.buckets.iter().map(|bucket| bucket.paid.u128()).sum();
+    paid.saturating_sub(won)
+}
+
+/// Returns why the house's part of the payout crosses a circuit limit of its denomination, if it does
+fn crossed_limit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    payout: &HeldPayout,
+) -> StdResult<Option<String>> {
+    let limits = read_circuit_limits(&deps.storage, &payout.denom)?;
+    let paid = payout.amount.u128() - payout.stake.u128();
+
+    if let Some(max) = limits.max_payout {
+        if paid > max.u128() {
+            return Ok(Some(format!("Payout of {} is above the maximum of {}", paid, max)));
+        }
+    }
+
+    if let Some(max) = limits.max_daily_game_payout {
+        let paid_today = match read_game(&deps.storage, &payout.game)? {
+            Some(game) if game.payout_day == env.block.time / SECONDS_PER_DAY => game.paid_today.u128(),
+            _ => 0,
+        };
+        if paid_today + paid > max.u128() {
+            return Ok(Some(format!(
+                "Game {} would pay {} today, above the maximum of {}",
+                payout.game,
+                paid_today + paid,
+                max
+            )));
+        }
+    }
+
+    if let Some(max) = limits.max_net_loss {
+        let mut window = read_loss_window(&deps.storage, &payout.denom)?;
+        let bucket = loss_bucket(&mut window, env.block.time, limits.net_loss_window);
+        bucket.paid = Uint128::from(bucket.paid.u128() + paid);
+        let loss = net_loss(&window);
+        if loss > max.u128() {
+            return Ok(Some(format!(
+                "Net loss of {} over the last {} seconds is above the maximum of {}",
+                loss,
+                limits.net_loss_window,
+                max
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Adds to the stats of the denomination and of the game, in total and for the current day
+fn update_stats<S: Storage>(
+    storage: &mut S,
+    env: &Env,
+    game: &HumanAddr,
+    denom: &str,
+    update: impl Fn(&mut Stats),
+) -> StdResult<()> {
+    let day = env.block.time / SECONDS_PER_DAY;
+    for scope in [denom, game.as_str()].iter() {
+        for bucket in [None, Some(day)].iter() {
+            let mut stats = read_stats(storage, scope, *bucket)?.unwrap_or_default();
+            update(&mut stats);
+            stats.day = day;
+            store_stats(storage, scope, *bucket, &stats)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Books a payout and sends it, without checking the circuit limits
+fn settle_payout<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: Env,
-    msg: HandleMsg,
-) -> StdResult<HandleResponse> {
-    match msg {
-        HandleMsg::UpdateGameAddress { address } => update_game_address(deps, address),
-        _ => after_initialization_transaction(deps, env, msg),
+    env: &Env,
+    payout: HeldPayout,
+) -> StdResult<CosmosMsg> {
+    let paid = Uint128::from(payout.amount.u128() - payout.stake.u128());
+    let amount = payout.amount;
+    update_stats(&mut deps.storage, env, &payout.game, &payout.denom, |stats| {
+        stats.paid_out = stats.paid_out + amount;
+        stats.biggest_payout = Uint128::from(stats.biggest_payout.u128().max(amount.u128()));
+    })?;
+
+    // A held payout may be paid after its game was removed from the registry
+    if let Some(mut game) = read_game(&deps.storage, &payout.game)? {
+        let day = env.block.time / SECONDS_PER_DAY;
+        if game.payout_day != day {
+            game.payout_day = day;
+            game.paid_today = Uint128::from(0_u128);
+        }
+        game.paid_today = game.paid_today + paid;
+        game.paid = game.paid + paid;
+        store_game(&mut deps.storage, &game)?;
     }
+
+    let limits = read_circuit_limits(&deps.storage, &payout.denom)?;
+    let mut window = read_loss_window(&deps.storage, &payout.denom)?;
+    let bucket = loss_bucket(&mut window, env.block.time, limits.net_loss_window);
+    bucket.paid = bucket.paid + paid;
+    store_loss_window(&mut deps.storage, &payout.denom, &window)?;
+
+    let mut fees = read_fees(&deps.storage, &payout.denom)?;
+    fees.house_paid = fees.house_paid + paid;
+    store_fees(&mut deps.storage, &payout.denom, &fees)?;
+
+    let mut pool = read_pool(&deps.storage, &payout.denom)?;
+    pool.escrowed = Uint128::from(pool.escrowed.u128().saturating_sub(payout.stake.u128()));
+    store_pool(&mut deps.storage, &payout.denom, &pool)?;
+
+    append_ledger_entry(&mut deps.storage, &mut LedgerEntry {
+        game: Some(payout.game),
+        round: Some(payout.round),
+        ..ledger_entry(env, LedgerAction::Payout, payout.to.clone(), &payout.denom, payout.amount)
+    })?;
+
+    pay_out(deps, env, &payout.denom, payout.to, payout.amount)
 }
 
-fn check_owner<S: Storage, A: Api, Q: Querier>(
+/// Books stakes the house won, they come out of escrow into the pool
+fn settle_won<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: &Env,
+    game: &HumanAddr,
+    denom: &str,
+    amount: u128,
 ) -> StdResult<()> {
-    let owner = read_owner(&deps.storage)?;
-    if owner != env.message.sender {
-        Err(StdError::unauthorized())
-    } else {
-        Ok(())
+    if amount == 0 {
+        return Ok(());
+    }
+
+    if let Some(mut game) = read_game(&deps.storage, game)? {
+        game.won = Uint128::from(game.won.u128() + amount);
+        store_game(&mut deps.storage, &game)?;
     }
+
+    let mut fees = read_fees(&deps.storage, denom)?;
+    fees.house_won = Uint128::from(fees.house_won.u128() + amount);
+    accrue_fees(&mut fees, read_fee_bps(&deps.storage)?);
+    store_fees(&mut deps.storage, denom, &fees)?;
+
+    let limits = read_circuit_limits(&deps.storage, denom)?;
+    let mut window = read_loss_window(&deps.storage, denom)?;
+    let bucket = loss_bucket(&mut window, env.block.time, limits.net_loss_window);
+    bucket.won = Uint128::from(bucket.won.u128() + amount);
+    store_loss_window(&mut deps.storage, denom, &window)?;
+
+    let mut pool = read_pool(&deps.storage, denom)?;
+    pool.escrowed = Uint128::from(pool.escrowed.u128().saturating_sub(amount));
+    store_pool(&mut deps.storage, denom, &pool)?;
+
+    Ok(())
 }
-pub fn after_initialization_transaction<S: Storage, A: Api, Q: Querier>(
+
+fn escrow_stake<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    mut game: GameInfo,
+    round_id: u64,
+    player: HumanAddr,
+    amount: Uint128,
+) -> HandleResult {
+    if amount.u128() == 0 {
+        return Err(StdError::generic_err("Stake should be sent along"));
+    }
+
+    let mut escrow = read_escrow(&deps.storage, &game.address, round_id)?;
+    match escrow.stakes.iter_mut().find(|stake| stake.player == player) {
+        Some(stake) => stake.amount = stake.amount + amount,
+        None => escrow.stakes.push(Stake { player: player.clone(), amount }),
+    }
+    store_escrow(&mut deps.storage, &game.address, round_id, &escrow)?;
+
+    if !game.open_rounds.contains(&round_id) {
+        game.open_rounds.push(round_id);
+        store_game(&mut deps.storage, &game)?;
+    }
+
+    let denom = game_denom(&game);
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    pool.escrowed = pool.escrowed + amount;
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    append_ledger_entry(&mut deps.storage, &mut LedgerEntry {
+        game: Some(game.address),
+        round: Some(round_id),
+        ..ledger_entry(env, LedgerAction::Stake, player, &denom, amount)
+    })?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn escrow_native_stake<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    msg: HandleMsg,
-) -> StdResult<HandleResponse> {
-    if is_pending_game_address(&deps.storage)? {
-        return Err(StdError::generic_err("Transaction made before contract was fully initialized".to_string()));
+    round_id: u64,
+    player: HumanAddr,
+) -> HandleResult {
+    let game = check_game(deps, &env, true)?;
+
+    let denom = game_denom(&game);
+    let amount: u128 = env.message.sent_funds.iter().filter(|coin| coin.denom == denom).map(|coin| coin.amount.u128()).sum();
+    escrow_stake(deps, &env, game, round_id, player, Uint128::from(amount))
+}
+
+fn contribute_jackpot<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    game: GameInfo,
+    round_id: u64,
+    player: HumanAddr,
+    amount: Uint128,
+) -> HandleResult {
+    if amount.u128() == 0 {
+        return Err(StdError::generic_err("Contribution should be sent along"));
     }
 
-    match msg {
-        HandleMsg::PayToWinner { amount, to } => withdraw(deps, env, amount,  to,false),
-        _ => administrative_transaction(deps, env, msg),
+    let denom = game_denom(&game);
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    pool.jackpot = pool.jackpot + amount;
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    append_ledger_entry(&mut deps.storage, &mut LedgerEntry {
+        game: Some(game.address),
+        round: Some(round_id),
+        ..ledger_entry(env, LedgerAction::Contribution, player, &denom, amount)
+    })?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn contribute_native_jackpot<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    round_id: u64,
+    player: HumanAddr,
+) -> HandleResult {
+    let game = check_game(deps, &env, true)?;
+
+    let denom = game_denom(&game);
+    let amount: u128 = env.message.sent_funds.iter().filter(|coin| coin.denom == denom).map(|coin| coin.amount.u128()).sum();
+    contribute_jackpot(deps, &env, game, round_id, player, Uint128::from(amount))
+}
+
+pub fn settle_round<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    round_id: u64,
+    results: Vec<Settlement>,
+) -> HandleResult {
+    let mut game = check_game(deps, &env, false)?;
+    if read_round_record(&deps.storage, &game.address, round_id)?.is_some() {
+        return Err(StdError::generic_err(format!("Round {} was already settled", round_id)));
+    }
+    if !game.open_rounds.contains(&round_id) {
+        return Err(StdError::generic_err(format!("Round {} has no stakes in escrow", round_id)));
+    }
+
+    let denom = game_denom(&game);
+    // Only shares on the paytable the table was created with are paid, so games the bank didn't create win no jackpots
+    let paytable: Vec<u16> = game.rules.as_ref()
+        .and_then(|rules| rules.jackpot.as_ref())
+        .map_or(vec![], |jackpot| jackpot.paytable.iter().map(|line| line.pool_bps).collect());
+    let mut escrow = read_escrow(&deps.storage, &game.address, round_id)?;
+    let stakes = escrow.stakes.iter().map(|stake| stake.amount.u128()).sum::<u128>();
+    let mut settled = escrow.stakes.clone();
+    let mut wagered = stakes;
+    let mut hands = escrow.stakes.len() as u64;
+
+    let mut won: u128 = 0;
+    let mut payouts = vec![];
+    let mut jackpots = vec![];
+    for result in results.iter() {
+        // Each stake is taken out of escrow once, so a player listed twice has no stake left the second time
+        let stake = match escrow.stakes.iter().position(|stake| stake.player == result.player) {
+            Some(index) => escrow.stakes.remove(index).amount.u128(),
+            None => return Err(StdError::generic_err(format!("{} has no unsettled stake in round {}", result.player, round_id))),
+        };
+        if result.payout.u128() > stake * MAX_PAYOUT_BPS / 10000 {
+            return Err(StdError::generic_err(format!("Payout to {} is above the maximum for its stake", result.player)));
+        }
+        if let Some(jackpot_bps) = result.jackpot_bps {
+            if jackpot_bps > 10000 {
+                return Err(StdError::generic_err("Jackpot share can't be more than 10000 basis points"));
+            }
+            if !paytable.contains(&jackpot_bps) {
+                return Err(StdError::generic_err(format!("Jackpot share of {} basis points isn't on the table's paytable", jackpot_bps)));
+            }
+            jackpots.push((result.player.clone(), jackpot_bps));
+        }
+
+        won += stake.saturating_sub(result.payout.u128());
+        if result.payout.u128() > 0 {
+            payouts.push(HeldPayout {
+                game: game.address.clone(),
+                to: result.player.clone(),
+                denom: denom.clone(),
+                amount: result.payout,
+                stake: Uint128::from(stake.min(result.payout.u128())),
+                round: round_id,
+            });
+        }
     }
+    won += escrow.stakes.iter().map(|stake| stake.amount.u128()).sum::<u128>();
+    remove_escrow(&mut deps.storage, &game.address, round_id);
+    store_round_record(&mut deps.storage, &game.address, &RoundRecord {
+        round_id,
+        settled_at: env.block.time,
+        stakes: Uint128::from(stakes),
+        results,
+    })?;
+
+    // Rounds are played in order, so an older round that is still open ended without a roundup and can't be settled anymore.
+    // Stakes left in it, like those of kicked players, are won by the house along with those of this round's losers
+    let (closed, open): (Vec<u64>, Vec<u64>) = game.open_rounds.iter().copied().partition(|round| *round <= round_id);
+    game.open_rounds = open;
+    store_game(&mut deps.storage, &game)?;
+
+    for round in closed.iter().filter(|round| **round != round_id) {
+        let mut escrow = read_escrow(&deps.storage, &game.address, *round)?;
+        let stakes = escrow.stakes.iter().map(|stake| stake.amount.u128()).sum::<u128>();
+        wagered += stakes;
+        hands += escrow.stakes.len() as u64;
+        settled.append(&mut escrow.stakes);
+        remove_escrow(&mut deps.storage, &game.address, *round);
+        store_round_record(&mut deps.storage, &game.address, &RoundRecord {
+            round_id: *round,
+            settled_at: env.block.time,
+            stakes: Uint128::from(stakes),
+            results: vec![],
+        })?;
+        won += stakes;
+    }
+    // Rewards are booked before the winnings, so the fee isn't charged on what goes to the referrers
+    accrue_referral_rewards(deps, &denom, &settled)?;
+    settle_won(deps, &env, &game.address, &denom, won)?;
+    update_stats(&mut deps.storage, &env, &game.address, &denom, |stats| {
+        stats.wagered = Uint128::from(stats.wagered.u128() + wagered);
+        stats.hands += hands;
+    })?;
+
+    let mut circuit = read_circuit(&deps.storage)?;
+    let mut messages = vec![];
+    for payout in payouts {
+        // Stakes that are only given back never trip the circuit and are never held
+        let house_pays = payout.amount > payout.stake;
+        if house_pays && !circuit.tripped {
+            if let Some(reason) = crossed_limit(deps, &env, &payout)? {
+                circuit.tripped = true;
+                circuit.reason = Some(reason);
+                circuit.tripped_at = Some(env.block.time);
+            }
+        }
+
+        if house_pays && circuit.tripped {
+            // Failing would undo tripping the circuit, so the payout is held until the operator resets it
+            reserve_held_payout(&mut deps.storage, &payout, true)?;
+            circuit.held.push(payout);
+        } else {
+            messages.push(settle_payout(deps, &env, payout)?);
+        }
+    }
+    store_circuit(&mut deps.storage, &circuit)?;
+
+    // Jackpots are paid out of the contributions rather than the house pool, so the circuit leaves them alone.
+    // Each winner takes their share of what the winners before them left
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    for (player, jackpot_bps) in jackpots {
+        let amount = pool.jackpot.u128() * u128::from(jackpot_bps) / 10000;
+        if amount == 0 {
+            continue;
+        }
+
+        pool.jackpot = Uint128::from(pool.jackpot.u128() - amount);
+        messages.push(pay_out(deps, &env, &denom, player.clone(), Uint128::from(amount))?);
+        append_ledger_entry(&mut deps.storage, &mut LedgerEntry {
+            game: Some(game.address.clone()),
+            round: Some(round_id),
+            ..ledger_entry(&env, LedgerAction::Jackpot, player, &denom, Uint128::from(amount))
+        })?;
+    }
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    Ok(HandleResponse {
+        messages,
+        log: if circuit.tripped { vec![log("circuit", "tripped")] } else { vec![] },
+        data: None,
+    })
 }
 
-pub fn administrative_transaction<S: Storage, A: Api, Q: Querier>(
+/// Credits the referrers of the players behind settled stakes with their share of the house edge.
+/// Rewards are a cost of the house, so they are booked as paid and set aside out of the pool until claimed
+fn accrue_referral_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    denom: &str,
+    stakes: &[Stake],
+) -> StdResult<()> {
+    let config = read_referral_config(&deps.storage)?;
+    if config.house_edge_bps == 0 || config.share_bps == 0 {
+        return Ok(());
+    }
+
+    let mut total: u128 = 0;
+    for stake in stakes {
+        let referrer = match read_referrer(&deps.storage, &stake.player)? {
+            Some(referrer) => referrer,
+            None => continue,
+        };
+        let reward = stake.amount.u128() * u128::from(config.house_edge_bps) * u128::from(config.share_bps) / 100_000_000;
+        if reward == 0 {
+            continue;
+        }
+
+        let mut rewards = read_referral_rewards(&deps.storage, &referrer, denom)?;
+        rewards.accrued = Uint128::from(rewards.accrued.u128() + reward);
+        store_referral_rewards(&mut deps.storage, &referrer, denom, &rewards)?;
+        total += reward;
+    }
+    if total == 0 {
+        return Ok(());
+    }
+
+    let mut pool = read_pool(&deps.storage, denom)?;
+    pool.referral_rewards = Uint128::from(pool.referral_rewards.u128() + total);
+    store_pool(&mut deps.storage, denom, &pool)?;
+
+    let mut fees = read_fees(&deps.storage, denom)?;
+    fees.house_paid = Uint128::from(fees.house_paid.u128() + total);
+    store_fees(&mut deps.storage, denom, &fees)
+}
+
+/// Records who referred a player, the first referrer registered for a player is kept
+pub fn register_referral<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    msg: HandleMsg,
-) -> StdResult<HandleResponse> {
-    check_owner(deps, &env)?;
-    match msg {
-        HandleMsg::ChangeOwner {new_owner} => change_owner(deps, &env, new_owner),
-        HandleMsg::EmergencyWithdrawAll {} => withdraw(
-            deps,
-            env.clone(),
-            // In this case, make sure you take all the balance away, don't use the data in the state
-            deps.querier.query_balance(env.contract.address, "uscrt")?.amount,
-            read_owner(&deps.storage)?,
-            true),
-        _ => panic!("Used non-administrative transaction as an administrative transaction"),
+    player: HumanAddr,
+    referrer: HumanAddr,
+) -> HandleResult {
+    check_game(deps, &env, false)?;
+    if player == referrer {
+        return Err(StdError::generic_err("A player can't refer themselves"));
+    }
+
+    if read_referrer(&deps.storage, &player)?.is_none() {
+        store_referrer(&mut deps.storage, &player, &referrer)?;
     }
+
+    Ok(HandleResponse::default())
 }
 
-fn get_bank_balance<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<Binary> {
-    let mut balance = deps.querier.query_balance(read_bank_address(&deps.storage)?, "uscrt")?.amount;
-    balance = Uint128::from(balance.u128() - (balance.u128() / 10) as u128); // 10% of the money is stored for the contract owner.
-    Ok(to_binary(&QueryAnswer::GetBankBalance { balance })?)
+pub fn claim_referral_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    denom: String,
+) -> HandleResult {
+    let sender = env.message.sender.clone();
+    let mut rewards = read_referral_rewards(&deps.storage, &sender, &denom)?;
+    let amount = rewards.accrued.u128() - rewards.claimed.u128();
+    if amount == 0 {
+        return Err(StdError::generic_err(format!("No referral rewards to claim in {}", denom)));
+    }
+
+    rewards.claimed = rewards.accrued;
+    store_referral_rewards(&mut deps.storage, &sender, &denom, &rewards)?;
+
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    pool.referral_rewards = Uint128::from(pool.referral_rewards.u128().saturating_sub(amount));
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    append_ledger_entry(&mut deps.storage, &mut ledger_entry(&env, LedgerAction::ReferralReward, sender.clone(), &denom, Uint128::from(amount)))?;
+    Ok(HandleResponse {
+        messages: vec![pay_out(deps, &env, &denom, sender, Uint128::from(amount))?],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ClaimReferralRewards { amount: Uint128::from(amount) })?),
+    })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetBankBalance {} => get_bank_balance(deps),
+pub fn set_referral_config<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    config: ReferralConfig,
+) -> HandleResult {
+    if config.house_edge_bps > 10000 || config.share_bps > 10000 {
+        return Err(StdError::generic_err("Referral house edge and share can't be more than 10000 basis points"));
+    }
+    store_referral_config(&mut deps.storage, &config)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetReferralConfig { status: ResponseStatus::Success })?),
+    })
+}
+
+pub fn set_circuit_limits<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    denom: String,
+    limits: CircuitLimits,
+) -> HandleResult {
+    if limits.net_loss_window == 0 {
+        return Err(StdError::generic_err("Net loss window should be set"));
+    }
+    store_circuit_limits(&mut deps.storage, &denom, &limits)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetCircuitLimits { status: ResponseStatus::Success })?),
+    })
+}
+
+/// Keeps the house's part of a held payout reserved, so LPs can't redeem it before the payout is paid or dropped
+fn reserve_held_payout<S: Storage>(
+    storage: &mut S,
+    payout: &HeldPayout,
+    held: bool,
+) -> StdResult<()> {
+    let house_part = payout.amount.u128() - payout.stake.u128();
+    let mut pool = read_pool(storage, &payout.denom)?;
+    pool.reserved = if held {
+        Uint128::from(pool.reserved.u128() + house_part)
+    } else {
+        Uint128::from(pool.reserved.u128().saturating_sub(house_part))
+    };
+    store_pool(storage, &payout.denom, &pool)
+}
+
+pub fn reset_circuit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    pay_held: bool,
+) -> HandleResult {
+    let circuit = read_circuit(&deps.storage)?;
+    if !circuit.tripped {
+        return Err(StdError::generic_err("Circuit isn't tripped"));
+    }
+
+    let held = circuit.held.len() as u32;
+    let mut messages = vec![];
+    for payout in circuit.held {
+        reserve_held_payout(&mut deps.storage, &payout, false)?;
+        if pay_held {
+            messages.push(settle_payout(deps, &env, payout)?);
+        } else if payout.stake.u128() > 0 {
+            // Only the house's part of a dropped payout is dropped, the player gets the stake back
+            let mut pool = read_pool(&deps.storage, &payout.denom)?;
+            pool.escrowed = Uint128::from(pool.escrowed.u128().saturating_sub(payout.stake.u128()));
+            store_pool(&mut deps.storage, &payout.denom, &pool)?;
+
+            append_ledger_entry(&mut deps.storage, &mut LedgerEntry {
+                game: Some(payout.game),
+                round: Some(payout.round),
+                ..ledger_entry(&env, LedgerAction::Payout, payout.to.clone(), &payout.denom, payout.stake)
+            })?;
+            messages.push(pay_out(deps, &env, &payout.denom, payout.to, payout.stake)?);
+        }
+    }
+    store_circuit(&mut deps.storage, &Circuit::default())?;
+
+    let (paid, dropped) = if pay_held { (held, 0) } else { (0, held) };
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::ResetCircuit { paid, dropped })?),
+    })
+}
+
+/// A shorter delay wouldn't leave LPs the time to redeem after an announcement
+fn check_emergency_delay(delay: u64) -> StdResult<()> {
+    if delay < MIN_EMERGENCY_DELAY {
+        return Err(StdError::generic_err(format!("Emergency withdrawal delay can't be less than {} seconds", MIN_EMERGENCY_DELAY)));
+    }
+
+    Ok(())
+}
+
+/// Sets the delay between announcing an emergency withdrawal and executing it
+pub fn set_emergency_delay<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    delay: u64,
+) -> HandleResult {
+    check_emergency_delay(delay)?;
+    store_emergency_delay(&mut deps.storage, &delay)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetEmergencyDelay { delay })?),
+    })
+}
+
+/// Gives LPs and players the delay to redeem and finish their rounds before the owner can take the bankroll
+pub fn announce_emergency_withdrawal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    if read_emergency_withdrawal(&deps.storage)?.is_some() {
+        return Err(StdError::generic_err("Emergency withdrawal was already announced"));
+    }
+
+    let executable_at = env.block.time + read_emergency_delay(&deps.storage)?;
+    store_emergency_withdrawal(&mut deps.storage, &Some(PendingWithdrawal { announced_at: env.block.time, executable_at }))?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("emergency_withdrawal", executable_at.to_string())],
+        data: Some(to_binary(&HandleAnswer::AnnounceEmergencyWithdrawal { executable_at })?),
+    })
+}
+
+/// Drops the announced emergency withdrawal, a new one waits the full delay again
+pub fn cancel_emergency_withdrawal<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+) -> HandleResult {
+    if read_emergency_withdrawal(&deps.storage)?.is_none() {
+        return Err(StdError::generic_err("No emergency withdrawal was announced"));
+    }
+
+    store_emergency_withdrawal(&mut deps.storage, &None)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CancelEmergencyWithdrawal {})?),
+    })
+}
+
+/// Dooms day withdrawal of every native coin and registered token the bank holds,
+/// the bank is left drained and rejects every later message but the viewing keys
+pub fn emergency_withdraw_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    match read_emergency_withdrawal(&deps.storage)? {
+        Some(pending) if env.block.time >= pending.executable_at => {}
+        Some(pending) => return Err(StdError::generic_err(format!("Emergency withdrawal can't be executed before {}", pending.executable_at))),
+        None => return Err(StdError::generic_err("Emergency withdrawal should be announced first")),
+    }
+    store_emergency_withdrawal(&mut deps.storage, &None)?;
+    store_status(&mut deps.storage, &ContractStatus::Drained)?;
+
+    let owner = read_owner(&deps.storage)?;
+    let mut messages = vec![];
+
+    // Make sure you take all the balance away, don't use the data in the state
+    let balances = deps.querier.query_all_balances(env.contract.address.clone())?;
+    for coin in &balances {
+        append_ledger_entry(&mut deps.storage, &mut ledger_entry(&env, LedgerAction::Withdrawal, owner.clone(), &coin.denom, coin.amount))?;
+    }
+    if !balances.is_empty() {
+        messages.push(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: owner.clone(),
+            amount: balances,
+        }));
+    }
+
+    for address in read_token_list(&deps.storage)? {
+        let mut pool = load_token_pool(deps, &address)?;
+        if pool.balance.u128() > 0 {
+            append_ledger_entry(&mut deps.storage, &mut ledger_entry(&env, LedgerAction::Withdrawal, owner.clone(), address.as_str(), pool.balance))?;
+            messages.push(token_transfer(&pool.token, owner.clone(), pool.balance)?);
+            pool.balance = Uint128::from(0_u128);
+            store_token_pool(&mut deps.storage, &pool)?;
+        }
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: None,
+    })
+}
+
+/// Denomination a game takes bets in, games the bank knows no rules of take bets in uscrt
+fn game_denom(game: &GameInfo) -> String {
+    match &game.rules {
+        Some(TableConfig { token: Some(token), .. }) => token.address.to_string(),
+        Some(rules) => rules.denom.clone(),
+        None => DEFAULT_DENOM.to_string(),
+    }
+}
+
+fn load_token_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<TokenPool> {
+    match read_token_pool(&deps.storage, address)? {
+        Some(pool) => Ok(pool),
+        None => Err(StdError::generic_err(format!("Token {} is not registered", address))),
+    }
+}
+
+fn token_transfer(token: &Token, recipient: HumanAddr, amount: Uint128) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(&snip20_msg::HandleMsg::Transfer { recipient, amount, padding: None })?,
+        send: vec![],
+    }))
+}
+
+/// Bank balance in a denomination, native coins are queried and tokens come from the bank's own records
+fn get_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<u128> {
+    match read_token_pool(&deps.storage, &HumanAddr(denom.to_string()))? {
+        Some(pool) => Ok(pool.balance.u128()),
+        None => Ok(deps.querier.query_balance(read_bank_address(&deps.storage)?, denom)?.amount.u128()),
+    }
+}
+
+/// Sends funds out of the bank, in a native coin or a registered token
+fn pay_out<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    denom: &str,
+    to: HumanAddr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    let balance = get_balance(deps, denom)?;
+    if amount.u128() > balance {
+        return Err(StdError::generic_err(format!(
+            "Insufficient bank balance, asked for: {}, balance is: {}",
+            amount,
+            balance
+        )));
+    }
+
+    match read_token_pool(&deps.storage, &HumanAddr(denom.to_string()))? {
+        Some(mut pool) => {
+            pool.balance = Uint128::from(pool.balance.u128() - amount.u128());
+            store_token_pool(&mut deps.storage, &pool)?;
+            token_transfer(&pool.token, to, amount)
+        }
+        None => Ok(CosmosMsg::Bank(BankMsg::Send {
+            from_address: env.contract.address.clone(),
+            to_address: to,
+            amount: vec![Coin::new(amount.u128(), denom)],
+        })),
+    }
+}
+
+pub fn register_token<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+    code_hash: String,
+) -> HandleResult {
+    if read_token_pool(&deps.storage, &address)?.is_some() {
+        return Err(StdError::generic_err(format!("Token {} is already registered", address)));
+    }
+
+    let token = Token { address: address.clone(), code_hash };
+    let mut tokens = read_token_list(&deps.storage)?;
+    tokens.push(address);
+    store_token_list(&mut deps.storage, &tokens)?;
+    store_token_pool(&mut deps.storage, &TokenPool {
+        token: token.clone(),
+        balance: Uint128::from(0_u128),
+    })?;
+
+    Ok(HandleResponse {
+        messages: vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.address,
+            callback_code_hash: token.code_hash,
+            msg: to_binary(&snip20_msg::HandleMsg::RegisterReceive {
+                code_hash: env.contract_code_hash,
+                padding: None,
+            })?,
+            send: vec![],
+        })],
+        log: vec![],
+        data: None,
+    })
+}
+
+pub fn receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    sender: HumanAddr,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> HandleResult {
+    let mut pool = load_token_pool(deps, &env.message.sender)?;
+    let msg: ReceiveMsg = match msg {
+        Some(msg) => from_binary(&msg)?,
+        None => return Err(StdError::generic_err("Tokens were sent without an action")),
+    };
+
+    // Received tokens count as part of the balance before the action runs, like native funds sent along
+    pool.balance = pool.balance + amount;
+    store_token_pool(&mut deps.storage, &pool)?;
+
+    let denom = env.message.sender.to_string();
+    match msg {
+        ReceiveMsg::EscrowStake { round_id, player } => {
+            // The game is the one that called the token's `Send`
+            let game = check_game_address(deps, &sender, true)?;
+            if game_denom(&game) != denom {
+                return Err(StdError::generic_err("Game doesn't take bets in this token".to_string()));
+            }
+
+            escrow_stake(deps, &env, game, round_id, player, amount)
+        }
+        ReceiveMsg::ContributeJackpot { round_id, player } => {
+            let game = check_game_address(deps, &sender, true)?;
+            if game_denom(&game) != denom {
+                return Err(StdError::generic_err("Game doesn't take bets in this token".to_string()));
+            }
+
+            contribute_jackpot(deps, &env, game, round_id, player, amount)
+        }
+        ReceiveMsg::Deposit {} => deposit_to_pool(deps, &env, &denom, &from, amount.u128()),
+    }
+}
+
+/// Value of the house pool in a denomination, all of it is owned by the LPs. Fees that weren't withdrawn yet belong to the owner
+/// and unclaimed referral rewards to the referrers
+fn get_pool_value<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<u128> {
+    let balance = get_balance(deps, denom)?;
+    pool_value_of(deps, denom, balance)
+}
+
+/// Part of the balance that isn't owed to anyone but the LPs
+fn pool_value_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+    balance: u128,
+) -> StdResult<u128> {
+    let fees = read_fees(&deps.storage, denom)?;
+    let pool = read_pool(&deps.storage, denom)?;
+
+    Ok(balance
+        .saturating_sub(fees.accrued.u128() - fees.withdrawn.u128())
+        .saturating_sub(pool.escrowed.u128())
+        .saturating_sub(pool.jackpot.u128())
+        .saturating_sub(pool.referral_rewards.u128()))
+}
+
+/// Responses are padded to a multiple of the block size, so it can be neither zero nor huge
+fn check_block_size(block_size: u16) -> StdResult<()> {
+    if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+        return Err(StdError::generic_err(format!("Block size should be between 1 and {}", MAX_BLOCK_SIZE)));
+    }
+
+    Ok(())
+}
+
+fn check_fee_bps(fee_bps: u16) -> StdResult<()> {
+    if fee_bps > MAX_FEE_BPS {
+        return Err(StdError::generic_err(format!("Fee can't be more than {} basis points", MAX_FEE_BPS)));
+    }
+
+    Ok(())
+}
+
+/// Charges the fee on any net winnings above the previous high
+fn accrue_fees(fees: &mut FeeLedger, fee_bps: u16) {
+    let net = fees.house_won.u128().saturating_sub(fees.house_paid.u128());
+    if net > fees.net_high_water.u128() {
+        let fee = (net - fees.net_high_water.u128()) * u128::from(fee_bps) / 10000;
+        fees.accrued = Uint128::from(fees.accrued.u128() + fee);
+        fees.net_high_water = Uint128::from(net);
+    }
+}
+
+pub fn withdraw_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    denom: String,
+    amount: Uint128,
+) -> HandleResult {
+    let mut fees = read_fees(&deps.storage, &denom)?;
+    let available = fees.accrued.u128() - fees.withdrawn.u128();
+    if amount.u128() > available {
+        return Err(StdError::generic_err(format!(
+            "Insufficient accrued fees, asked for: {}, available: {}",
+            amount,
+            available
+        )));
+    }
+
+    fees.withdrawn = fees.withdrawn + amount;
+    store_fees(&mut deps.storage, &denom, &fees)?;
+
+    let owner = read_owner(&deps.storage)?;
+    append_ledger_entry(&mut deps.storage, &mut ledger_entry(&env, LedgerAction::Withdrawal, owner.clone(), &denom, amount))?;
+    Ok(HandleResponse {
+        messages: vec![pay_out(deps, &env, &denom, owner, amount)?],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::WithdrawFees { amount })?),
+    })
+}
+
+pub fn set_fee<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    fee_bps: u16,
+) -> HandleResult {
+    check_fee_bps(fee_bps)?;
+    store_fee_bps(&mut deps.storage, &fee_bps)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetFee { fee_bps })?),
+    })
+}
+
+pub fn set_denom_exposure_limit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    denom: String,
+    exposure_limit: Option<Uint128>,
+) -> HandleResult {
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    pool.exposure_limit = exposure_limit;
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetDenomExposureLimit { status: ResponseStatus::Success })?),
+    })
+}
+
+pub fn deposit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> HandleResult {
+    if env.message.sent_funds.len() != 1 {
+        return Err(StdError::generic_err("Deposit should be sent in a single native coin"));
+    }
+
+    let coin = &env.message.sent_funds[0];
+    deposit_to_pool(deps, &env, &coin.denom, &env.message.sender, coin.amount.u128())
+}
+
+fn deposit_to_pool<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    denom: &str,
+    depositor: &HumanAddr,
+    amount: u128,
+) -> HandleResult {
+    if amount == 0 {
+        return Err(StdError::generic_err("Deposit amount should be set"));
+    }
+
+    // Sent funds are already part of the bank balance, obligations may exceed what's left without them
+    let balance = get_balance(deps, denom)?.saturating_sub(amount);
+    let pool_value = pool_value_of(deps, denom, balance)?;
+    let mut pool = read_pool(&deps.storage, denom)?;
+
+    if pool.total_shares.u128() == 0 && pool_value > 0 {
+        // Funds that were sent to the bank before the first deposit belong to the owner
+        let owner = read_owner(&deps.storage)?;
+        let owner_shares = read_lp_shares(&deps.storage, denom, &owner)?;
+        store_lp_shares(&mut deps.storage, denom, &owner, &Uint128::from(owner_shares.u128() + pool_value))?;
+        pool.total_shares = Uint128::from(pool_value);
+    }
+
+    let shares = if pool.total_shares.u128() == 0 {
+        amount
+    } else {
+        if pool_value == 0 {
+            return Err(StdError::generic_err("The pool has no funds left, deposits are not accepted"));
+        }
+
+        amount * pool.total_shares.u128() / pool_value
+    };
+
+    if shares == 0 {
+        return Err(StdError::generic_err("Deposit is too small to mint a share"));
+    }
+
+    let lp_shares = read_lp_shares(&deps.storage, denom, depositor)?;
+    store_lp_shares(&mut deps.storage, denom, depositor, &Uint128::from(lp_shares.u128() + shares))?;
+    pool.total_shares = Uint128::from(pool.total_shares.u128() + shares);
+    store_pool(&mut deps.storage, denom, &pool)?;
+
+    append_ledger_entry(&mut deps.storage, &mut ledger_entry(env, LedgerAction::Deposit, depositor.clone(), denom, Uint128::from(amount)))?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Deposit { shares: Uint128::from(shares) })?),
+    })
+}
+
+pub fn redeem<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    denom: String,
+    shares: Uint128,
+) -> HandleResult {
+    let lp_shares = read_lp_shares(&deps.storage, &denom, &env.message.sender)?;
+    if shares.u128() == 0 || shares > lp_shares {
+        return Err(StdError::generic_err(format!(
+            "Can't redeem {} shares, owned shares: {}",
+            shares,
+            lp_shares
+        )));
+    }
+
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    let pool_value = get_pool_value(deps, &denom)?;
+    let amount = shares.u128() * pool_value / pool.total_shares.u128();
+    if amount > pool_value.saturating_sub(pool.reserved.u128()) {
+        return Err(StdError::generic_err("Funds are reserved for bets in play, try to redeem less shares or wait for the round to end"));
+    }
+
+    store_lp_shares(&mut deps.storage, &denom, &env.message.sender, &Uint128::from(lp_shares.u128() - shares.u128()))?;
+    pool.total_shares = Uint128::from(pool.total_shares.u128() - shares.u128());
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    let mut messages = vec![];
+    if amount > 0 {
+        let sender = env.message.sender.clone();
+        append_ledger_entry(&mut deps.storage, &mut ledger_entry(&env, LedgerAction::Withdrawal, sender.clone(), &denom, Uint128::from(amount)))?;
+        messages.push(pay_out(deps, &env, &denom, sender, Uint128::from(amount))?);
+    }
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::Redeem { amount: Uint128::from(amount) })?),
+    })
+}
+
+pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> HandleResult {
+    let key = ViewingKey::create(&mut deps.storage, &env, &env.message.sender, entropy.as_bytes());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+    })
+}
+
+pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> HandleResult {
+    ViewingKey::set(&mut deps.storage, &env.message.sender, key.as_str());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey { status: ResponseStatus::Success })?),
+    })
+}
+
+/// Registry entry of the calling game. Disabled games are let through only to settle the bets already in play
+fn check_game<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    new_bets: bool,
+) -> StdResult<GameInfo> {
+    check_game_address(deps, &env.message.sender, new_bets)
+}
+
+fn check_game_address<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    new_bets: bool,
+) -> StdResult<GameInfo> {
+    let game = match read_game(&deps.storage, address)? {
+        Some(game) => game,
+        None => return Err(StdError::generic_err("Only a registered game contract can use the bank".to_string())),
+    };
+
+    if new_bets && !game.enabled {
+        return Err(StdError::generic_err("Game is disabled and can't take new bets".to_string()));
+    }
+
+    if new_bets && read_status(&deps.storage)? != ContractStatus::Normal {
+        return Err(StdError::generic_err("Bank isn't taking new bets".to_string()));
+    }
+
+    if new_bets && read_circuit(&deps.storage)?.tripped {
+        return Err(StdError::generic_err("Bank circuit breaker is tripped, new bets are paused".to_string()));
+    }
+
+    if new_bets && read_emergency_withdrawal(&deps.storage)?.is_some() {
+        return Err(StdError::generic_err("Emergency withdrawal was announced, new bets are paused".to_string()));
+    }
+
+    Ok(game)
+}
+
+fn load_game<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<GameInfo> {
+    match read_game(&deps.storage, address)? {
+        Some(game) => Ok(game),
+        None => Err(StdError::generic_err(format!("Game {} is not registered", address))),
+    }
+}
+
+fn game_response(data: HandleAnswer) -> HandleResult {
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&data)?),
+    })
+}
+
+fn register_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    game: &GameInfo,
+) -> StdResult<()> {
+    if read_game(&deps.storage, &game.address)?.is_some() {
+        return Err(StdError::generic_err(format!("Game {} is already registered", game.address)));
+    }
+
+    let mut games = read_game_list(&deps.storage)?;
+    games.push(game.address.clone());
+    store_game_list(&mut deps.storage, &games)?;
+    store_game(&mut deps.storage, game)
+}
+
+pub fn add_game<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    address: HumanAddr,
+    exposure_limit: Option<Uint128>,
+) -> HandleResult {
+    register_game(deps, &GameInfo::new(address, None, exposure_limit))?;
+
+    game_response(HandleAnswer::UpdateGame { status: ResponseStatus::Success })
+}
+
+pub fn remove_game_from_registry<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    address: HumanAddr,
+) -> HandleResult {
+    let game = load_game(deps, &address)?;
+    if game.reserved.u128() > 0 || !game.open_rounds.is_empty() {
+        return Err(StdError::generic_err("Game has bets in play, disable it and wait for them to settle"));
+    }
+
+    let mut games = read_game_list(&deps.storage)?;
+    games.retain(|game_address| *game_address != address);
+    store_game_list(&mut deps.storage, &games)?;
+    remove_game(&mut deps.storage, &address);
+
+    game_response(HandleAnswer::UpdateGame { status: ResponseStatus::Success })
+}
+
+pub fn set_game_enabled<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    address: HumanAddr,
+    enabled: bool,
+) -> HandleResult {
+    let mut game = load_game(deps, &address)?;
+    game.enabled = enabled;
+    store_game(&mut deps.storage, &game)?;
+
+    game_response(HandleAnswer::UpdateGame { status: ResponseStatus::Success })
+}
+
+pub fn set_game_exposure_limit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    address: HumanAddr,
+    exposure_limit: Option<Uint128>,
+) -> HandleResult {
+    let mut game = load_game(deps, &address)?;
+    game.exposure_limit = exposure_limit;
+    store_game(&mut deps.storage, &game)?;
+
+    game_response(HandleAnswer::UpdateGame { status: ResponseStatus::Success })
+}
+
+/// Funds that can back new bets in a denomination, the exposure of bets in play is kept aside
+fn get_free_funds<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<u128> {
+    let pool_value = get_pool_value(deps, denom)?;
+    let reserved = read_pool(&deps.storage, denom)?.reserved.u128();
+
+    Ok(pool_value.saturating_sub(reserved))
+}
+
+pub fn reserve_exposure<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> HandleResult {
+    let mut game = check_game(deps, &env, true)?;
+    let denom = game_denom(&game);
+
+    let free = get_free_funds(deps, &denom)?;
+    if amount.u128() > free {
+        return Err(StdError::generic_err(format!(
+            "Insufficient free bank funds, asked to reserve: {}, free funds are: {}",
+            amount,
+            free
+        )));
+    }
+
+    if let Some(limit) = game.exposure_limit {
+        if game.reserved.u128() + amount.u128() > limit.u128() {
+            return Err(StdError::generic_err(format!(
+                "Game exposure limit reached, asked to reserve: {}, reserved: {}, limit: {}",
+                amount,
+                game.reserved,
+                limit
+            )));
+        }
+    }
+
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    if let Some(limit) = pool.exposure_limit {
+        if pool.reserved.u128() + amount.u128() > limit.u128() {
+            return Err(StdError::generic_err(format!(
+                "Exposure limit for {} reached, asked to reserve: {}, reserved: {}, limit: {}",
+                denom,
+                amount,
+                pool.reserved,
+                limit
+            )));
+        }
+    }
+
+    game.reserved = game.reserved + amount;
+    store_game(&mut deps.storage, &game)?;
+
+    pool.reserved = pool.reserved + amount;
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    Ok(HandleResponse::default())
+}
+
+pub fn release_exposure<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    amount: Uint128,
+) -> HandleResult {
+    let mut game = check_game(deps, &env, false)?;
+
+    // A game can only release what it reserved itself
+    let amount = Uint128::from(amount.u128().min(game.reserved.u128()));
+    game.reserved = Uint128::from(game.reserved.u128() - amount.u128());
+    store_game(&mut deps.storage, &game)?;
+
+    let denom = game_denom(&game);
+    let mut pool = read_pool(&deps.storage, &denom)?;
+    pool.reserved = Uint128::from(pool.reserved.u128().saturating_sub(amount.u128()));
+    store_pool(&mut deps.storage, &denom, &pool)?;
+
+    Ok(HandleResponse::default())
+}
+
+/// A game instantiated by the bank registers itself once, from its own init. Other games are added by the owner
+pub fn update_game_address<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: HumanAddr,
+) -> HandleResult {
+    if !is_pending_game_address(&deps.storage)? {
+        return Err(StdError::generic_err("No game is waiting for registration, the owner can add games to the registry".to_string()));
+    }
+
+    if env.message.sender != address {
+        return Err(StdError::generic_err("Only the game contract can register itself".to_string()));
+    }
+
+    let mut game = read_pending_game(&deps.storage)?;
+    game.address = address;
+    register_game(deps, &game)?;
+    unset_pending_game_address(&mut deps.storage)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: None,
+    })
+}
+
+pub fn set_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    status: ContractStatus,
+) -> HandleResult {
+    if status == ContractStatus::Drained {
+        return Err(StdError::generic_err("Only the emergency withdrawal drains the bank"));
+    }
+    store_status(&mut deps.storage, &status)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetStatus { status: ResponseStatus::Success })?),
+    })
+}
+
+pub fn handle<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    match read_status(&deps.storage)? {
+        ContractStatus::StopAll => match msg {
+            HandleMsg::SetStatus { .. }
+            | HandleMsg::AnnounceEmergencyWithdrawal { .. }
+            | HandleMsg::CancelEmergencyWithdrawal { .. }
+            | HandleMsg::EmergencyWithdrawAll { .. }
+            | HandleMsg::CreateViewingKey { .. }
+            | HandleMsg::SetViewingKey { .. } => {}
+            _ => return Err(StdError::generic_err("Bank is stopped")),
+        },
+        // The pools, escrows and fees left in the state aren't backed by any funds anymore
+        ContractStatus::Drained => match msg {
+            HandleMsg::CreateViewingKey { .. }
+            | HandleMsg::SetViewingKey { .. } => {}
+            _ => return Err(StdError::generic_err("Bank was drained by the emergency withdrawal")),
+        },
+        _ => {}
+    }
+
+    let response = match msg {
+        HandleMsg::UpdateGameAddress { address, .. } => update_game_address(deps, env, address),
+        _ => after_initialization_transaction(deps, env, msg),
+    };
+
+    pad_handle_result(response, usize::from(read_block_size(&deps.storage)?))
+}
+
+fn check_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<()> {
+    let owner = read_owner(&deps.storage)?;
+    if owner != env.message.sender {
+        Err(StdError::unauthorized())
+    } else {
+        Ok(())
+    }
+}
+
+fn check_operator<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+) -> StdResult<()> {
+    let operator = read_operator(&deps.storage)?;
+    if operator != env.message.sender {
+        Err(StdError::unauthorized())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn after_initialization_transaction<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    if is_pending_game_address(&deps.storage)? {
+        return Err(StdError::generic_err("Transaction made before contract was fully initialized".to_string()));
+    }
+
+    match msg {
+        HandleMsg::EscrowStake { round_id, player, .. } => escrow_native_stake(deps, env, round_id, player),
+        HandleMsg::ContributeJackpot { round_id, player, .. } => contribute_native_jackpot(deps, env, round_id, player),
+        HandleMsg::SettleRound { round_id, results, .. } => settle_round(deps, env, round_id, results),
+        HandleMsg::RegisterReferral { player, referrer, .. } => register_referral(deps, env, player, referrer),
+        HandleMsg::ClaimReferralRewards { denom, .. } => claim_referral_rewards(deps, env, denom),
+        HandleMsg::Receive { sender, from, amount, msg, .. } => receive(deps, env, sender, from, amount, msg),
+        HandleMsg::ReserveExposure { amount, .. } => reserve_exposure(deps, env, amount),
+        HandleMsg::ReleaseExposure { amount, .. } => release_exposure(deps, env, amount),
+        HandleMsg::Deposit { .. } => deposit(deps, env),
+        HandleMsg::Redeem { denom, shares, .. } => redeem(deps, env, denom, shares),
+        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps, env, entropy),
+        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
+        HandleMsg::AcceptOwnership { .. } => accept_ownership(deps, env),
+        HandleMsg::SetFee { .. }
+        | HandleMsg::DisableGame { .. }
+        | HandleMsg::EnableGame { .. }
+        | HandleMsg::SetGameExposureLimit { .. }
+        | HandleMsg::SetDenomExposureLimit { .. }
+        | HandleMsg::SetCircuitLimits { .. }
+        | HandleMsg::ResetCircuit { .. }
+        | HandleMsg::SetReferralConfig { .. }
+        | HandleMsg::SetStatus { .. } => operator_transaction(deps, env, msg),
+        _ => administrative_transaction(deps, env, msg),
+    }
+}
+
+/// Pausing and configuration, kept apart from the owner's treasury permissions
+pub fn operator_transaction<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    check_operator(deps, &env)?;
+    match msg {
+        HandleMsg::SetFee { fee_bps, .. } => set_fee(deps, fee_bps),
+        HandleMsg::DisableGame { address, .. } => set_game_enabled(deps, address, false),
+        HandleMsg::EnableGame { address, .. } => set_game_enabled(deps, address, true),
+        HandleMsg::SetGameExposureLimit { address, exposure_limit, .. } => set_game_exposure_limit(deps, address, exposure_limit),
+        HandleMsg::SetDenomExposureLimit { denom, exposure_limit, .. } => set_denom_exposure_limit(deps, denom, exposure_limit),
+        HandleMsg::SetCircuitLimits { denom, limits, .. } => set_circuit_limits(deps, denom, limits),
+        HandleMsg::ResetCircuit { pay_held, .. } => reset_circuit(deps, env, pay_held),
+        HandleMsg::SetReferralConfig { config, .. } => set_referral_config(deps, config),
+        HandleMsg::SetStatus { status, .. } => set_status(deps, status),
+        _ => panic!("Used non-operator transaction as an operator transaction"),
+    }
+}
+
+pub fn administrative_transaction<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: HandleMsg,
+) -> StdResult<HandleResponse> {
+    check_owner(deps, &env)?;
+    match msg {
+        HandleMsg::ProposeOwner { new_owner, .. } => propose_owner(deps, new_owner),
+        HandleMsg::CancelOwnershipProposal { .. } => cancel_ownership_proposal(deps),
+        HandleMsg::SetOperator { operator, .. } => set_operator(deps, operator),
+        HandleMsg::WithdrawFees { denom, amount, .. } => withdraw_fees(deps, env, denom, amount),
+        HandleMsg::CreateGame { code_id, code_hash, rules, exposure_limit, .. } => create_game(deps, env, code_id, code_hash, rules, exposure_limit),
+        HandleMsg::AddGame { address, exposure_limit, .. } => add_game(deps, address, exposure_limit),
+        HandleMsg::RegisterToken { address, code_hash, .. } => register_token(deps, env, address, code_hash),
+        HandleMsg::RemoveGame { address, .. } => remove_game_from_registry(deps, address),
+        HandleMsg::AnnounceEmergencyWithdrawal { .. } => announce_emergency_withdrawal(deps, env),
+        HandleMsg::CancelEmergencyWithdrawal { .. } => cancel_emergency_withdrawal(deps),
+        HandleMsg::EmergencyWithdrawAll { .. } => emergency_withdraw_all(deps, env),
+        HandleMsg::SetEmergencyDelay { delay, .. } => set_emergency_delay(deps, delay),
+        _ => panic!("Used non-administrative transaction as an administrative transaction"),
+    }
+}
+
+fn get_bank_balance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<Binary> {
+    let total = Uint128::from(get_balance(deps, denom)?);
+    let reserved = read_pool(&deps.storage, denom)?.reserved;
+    let free = Uint128::from(get_free_funds(deps, denom)?);
+    Ok(to_binary(&QueryAnswer::GetBankBalance { free, reserved, total })?)
+}
+
+fn get_pool_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<Binary> {
+    let pool = read_pool(&deps.storage, denom)?;
+    let pool_value = get_pool_value(deps, denom)?;
+    let share_price = if pool.total_shares.u128() == 0 {
+        Decimal::one()
+    } else {
+        Decimal::from_ratio(pool_value, pool.total_shares.u128())
+    };
+
+    to_binary(&QueryAnswer::GetPoolInfo {
+        total_shares: pool.total_shares,
+        pool_value: Uint128::from(pool_value),
+        share_price,
+    })
+}
+
+fn stats_view(stats: &Stats) -> StatsView {
+    let wagered = stats.wagered.u128();
+    let paid_out = stats.paid_out.u128();
+    let house_edge_bps = if wagered == 0 {
+        0
+    } else {
+        ((wagered as i128 - paid_out as i128) * 10000 / wagered as i128).max(i128::from(i32::MIN)) as i32
+    };
+
+    StatsView {
+        day: stats.day,
+        wagered: stats.wagered,
+        paid_out: stats.paid_out,
+        hands: stats.hands,
+        biggest_payout: stats.biggest_payout,
+        house_profit: Uint128::from(wagered.saturating_sub(paid_out)),
+        house_loss: Uint128::from(paid_out.saturating_sub(wagered)),
+        house_edge_bps,
+    }
+}
+
+fn get_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+    game: Option<HumanAddr>,
+    days: Option<u32>,
+) -> StdResult<Binary> {
+    let scope = match game {
+        Some(game) => game.to_string(),
+        None => denom.to_string(),
+    };
+
+    let total = read_stats(&deps.storage, &scope, None)?.unwrap_or_default();
+    let days = u64::from(days.unwrap_or(0).min(MAX_STATS_DAYS));
+    let mut daily = vec![];
+    for day in ((total.day + 1).saturating_sub(days)..=total.day).rev() {
+        if let Some(stats) = read_stats(&deps.storage, &scope, Some(day))? {
+            daily.push(stats_view(&stats));
+        }
+    }
+
+    to_binary(&QueryAnswer::GetStats { total: stats_view(&total), daily })
+}
+
+fn get_emergency_withdrawal<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetEmergencyWithdrawal {
+        pending: read_emergency_withdrawal(&deps.storage)?,
+        delay: read_emergency_delay(&deps.storage)?,
+    })
+}
+
+fn get_referral_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetReferralConfig { config: read_referral_config(&deps.storage)? })
+}
+
+fn get_referral_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    denom: &str,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetReferralRewards { rewards: read_referral_rewards(&deps.storage, address, denom)? })
+}
+
+fn get_jackpot<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetJackpot { pool: read_pool(&deps.storage, denom)?.jackpot })
+}
+
+fn get_fees<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<Binary> {
+    let fees = read_fees(&deps.storage, denom)?;
+
+    to_binary(&QueryAnswer::GetFees {
+        fee_bps: read_fee_bps(&deps.storage)?,
+        accrued: fees.accrued,
+        withdrawn: fees.withdrawn,
+        available: Uint128::from(fees.accrued.u128() - fees.withdrawn.u128()),
+    })
+}
+
+fn get_roles<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetRoles {
+        owner: read_owner(&deps.storage)?,
+        operator: read_operator(&deps.storage)?,
+        proposed_owner: read_proposed_owner(&deps.storage)?,
+    })
+}
+
+fn get_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetStatus { status: read_status(&deps.storage)? })
+}
+
+fn get_circuit<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    denom: &str,
+) -> StdResult<Binary> {
+    let circuit = read_circuit(&deps.storage)?;
+    to_binary(&QueryAnswer::GetCircuit {
+        tripped: circuit.tripped,
+        reason: circuit.reason,
+        tripped_at: circuit.tripped_at,
+        held_payouts: circuit.held.len() as u32,
+        limits: read_circuit_limits(&deps.storage, denom)?,
+        net_loss: Uint128::from(net_loss(&read_loss_window(&deps.storage, denom)?)),
+    })
+}
+
+fn list_games<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let mut games = vec![];
+    for address in read_game_list(&deps.storage)? {
+        games.push(load_game(deps, &address)?);
+    }
+
+    to_binary(&QueryAnswer::ListGames { games })
+}
+
+fn get_lp_position<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    denom: &str,
+) -> StdResult<Binary> {
+    let pool = read_pool(&deps.storage, denom)?;
+    let shares = read_lp_shares(&deps.storage, denom, address)?;
+    let value = if pool.total_shares.u128() == 0 {
+        0
+    } else {
+        shares.u128() * get_pool_value(deps, denom)? / pool.total_shares.u128()
+    };
+
+    to_binary(&QueryAnswer::GetLpPosition { shares, value: Uint128::from(value) })
+}
+
+fn get_ledger<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Binary> {
+    if *address != read_owner(&deps.storage)? {
+        return Err(StdError::unauthorized());
+    }
+
+    let (entries, total) = read_ledger(&deps.storage, start_after, limit.unwrap_or(MAX_LEDGER_PAGE).min(MAX_LEDGER_PAGE))?;
+    to_binary(&QueryAnswer::GetLedger { entries, total })
+}
+
+fn get_round_record<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    game: &HumanAddr,
+    round_id: u64,
+) -> StdResult<Binary> {
+    if *address != read_owner(&deps.storage)? {
+        return Err(StdError::unauthorized());
+    }
+
+    let record = read_round_record(&deps.storage, game, round_id)?;
+    to_binary(&QueryAnswer::GetRoundRecord { record })
+}
+
+fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let (address, key) = msg.get_validation_params();
+    if ViewingKey::check(&deps.storage, address, key).is_err() {
+        return to_binary(&QueryAnswer::ViewingKeyError {
+            msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+        });
+    }
+
+    match msg {
+        QueryMsg::GetLpPosition { address, denom, .. } => get_lp_position(deps, &address, &denom),
+        QueryMsg::GetLedger { address, start_after, limit, .. } => get_ledger(deps, &address, start_after, limit),
+        QueryMsg::GetRoundRecord { address, game, round_id, .. } => get_round_record(deps, &address, &game, round_id),
+        QueryMsg::GetReferralRewards { address, denom, .. } => get_referral_rewards(deps, &address, &denom),
+        _ => panic!("This query type does not require authentication"),
+    }
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let response = match msg {
+        QueryMsg::GetBankBalance { denom } => get_bank_balance(deps, &denom),
+        QueryMsg::GetPoolInfo { denom } => get_pool_info(deps, &denom),
+        QueryMsg::GetFees { denom } => get_fees(deps, &denom),
+        QueryMsg::GetRoles {} => get_roles(deps),
+        QueryMsg::GetStatus {} => get_status(deps),
+        QueryMsg::GetJackpot { denom } => get_jackpot(deps, &denom),
+        QueryMsg::GetEmergencyWithdrawal {} => get_emergency_withdrawal(deps),
+        QueryMsg::GetReferralConfig {} => get_referral_config(deps),
+        QueryMsg::GetStats { denom, game, days } => get_stats(deps, &denom, game, days),
+        QueryMsg::GetCircuit { denom } => get_circuit(deps, &denom),
+        QueryMsg::ListGames {} => list_games(deps),
+        _ => viewing_keys_queries(deps, msg),
+    };
+
+    pad_query_result(response, usize::from(read_block_size(&deps.storage)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coins, from_binary};
+    use crate::msg::ReferralRewards;
+    use crate::contract::game_msg::{JackpotConfig, JackpotHand, JackpotPay};
+
+    fn escrow(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, game: &str, round_id: u64, player: &str, amount: u128) {
+        let msg = HandleMsg::EscrowStake { round_id, player: HumanAddr(player.to_string()), padding: None };
+        handle(deps, mock_env(game, &coins(amount, "uscrt")), msg).unwrap();
+    }
+
+    fn init_msg() -> InitMsg {
+        InitMsg {
+            game_contract_code_id: 1,
+            game_contract_code_hash: "".to_string(),
+            secret: 1234,
+            block_size: None,
+            game_config: None,
+            fee_bps: None,
+            emergency_delay: None,
+        }
+    }
+
+    /// Bank initialized by "owner" with "game" registered, the mock querier reports `balance` as the bank's funds
+    fn setup_bank(balance: &[Coin], msg: InitMsg) -> Extern<MockStorage, MockApi, MockQuerier> {
+        let mut deps = mock_dependencies(20, balance);
+        init(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("game".to_string()), padding: None };
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+        deps
+    }
+
+    fn settle(deps: &mut Extern<MockStorage, MockApi, MockQuerier>, game: &str, round_id: u64, payouts: &[(&str, u128)]) -> HandleResult {
+        let results = payouts.iter().map(|(player, payout)| Settlement { player: HumanAddr(player.to_string()), payout: Uint128(*payout), jackpot_bps: None }).collect();
+        handle(deps, mock_env(game, &[]), HandleMsg::SettleRound { round_id, results, padding: None })
+    }
+
+    #[test]
+    fn test_deposit_and_redeem() {
+        // The mock querier reports a fixed balance, 1000 uscrt sent before any LP joined and a deposit of 500
+        let mut deps = setup_bank(&coins(1500, "uscrt"), init_msg());
+
+        let msg = HandleMsg::Deposit { padding: None };
+        let res = handle(&mut deps, mock_env("lp", &coins(500, "uscrt")), msg).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Deposit { shares } => assert_eq!(shares, Uint128(500)),
+            _ => panic!("Unexpected handle answer"),
+        }
+
+        assert_eq!(read_lp_shares(&deps.storage, "uscrt", &HumanAddr("owner".to_string())).unwrap(), Uint128(1000));
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().total_shares, Uint128(1500));
+
+        let msg = HandleMsg::Redeem { denom: "uscrt".to_string(), shares: Uint128(501), padding: None };
+        let res = handle(&mut deps, mock_env("lp", &[]), msg);
+        assert!(res.is_err(), "LP can't redeem more shares than they own");
+
+        let msg = HandleMsg::Redeem { denom: "uscrt".to_string(), shares: Uint128(300), padding: None };
+        let res = handle(&mut deps, mock_env("lp", &[]), msg).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Redeem { amount } => assert_eq!(amount, Uint128(300)),
+            _ => panic!("Unexpected handle answer"),
+        }
+
+        assert_eq!(read_lp_shares(&deps.storage, "uscrt", &HumanAddr("lp".to_string())).unwrap(), Uint128(200));
+
+        // Escrowed stakes of 1200 exceed the 1000 the bank held before the deposit
+        let mut deps = setup_bank(&coins(1500, "uscrt"), init_msg());
+        escrow(&mut deps, "game", 1, "player", 1200);
+        let res = handle(&mut deps, mock_env("lp", &coins(500, "uscrt")), HandleMsg::Deposit { padding: None }).unwrap();
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::Deposit { shares } => assert_eq!(shares, Uint128(500)),
+            _ => panic!("Unexpected handle answer"),
+        }
+        assert_eq!(read_lp_shares(&deps.storage, "uscrt", &HumanAddr("owner".to_string())).unwrap(), Uint128(0));
+    }
+
+    #[test]
+    fn test_exposure() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+        let bank_balance = |deps: &Extern<MockStorage, MockApi, MockQuerier>| -> (Uint128, Uint128, Uint128) {
+            match from_binary(&query(deps, QueryMsg::GetBankBalance { denom: "uscrt".to_string() }).unwrap()).unwrap() {
+                QueryAnswer::GetBankBalance { free, reserved, total } => (free, reserved, total),
+                _ => panic!("Unexpected query answer"),
+            }
+        };
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(3000), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only registered games can reserve exposure");
+
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+        assert_eq!(bank_balance(&deps), (Uint128(2000), Uint128(3000), Uint128(5000)));
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(2001), padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "Reserved funds can't back another bet");
+
+        let msg = HandleMsg::SetDenomExposureLimit { denom: "uscrt".to_string(), exposure_limit: Some(Uint128(3500)), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(501), padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "Exposure can't go over the denomination limit");
+        assert_eq!(bank_balance(&deps), (Uint128(2000), Uint128(3000), Uint128(5000)));
+
+        let msg = HandleMsg::ReleaseExposure { amount: Uint128(1000), padding: None };
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+        assert_eq!(bank_balance(&deps), (Uint128(3000), Uint128(2000), Uint128(5000)));
+
+        let msg = HandleMsg::ReleaseExposure { amount: Uint128(5000), padding: None };
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+        assert_eq!(bank_balance(&deps), (Uint128(5000), Uint128(0), Uint128(5000)), "A game only releases what it reserved");
+    }
+
+    #[test]
+    fn test_fees() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), InitMsg { fee_bps: Some(1000), ..init_msg() });
+
+        escrow(&mut deps, "game", 1, "loser", 1000);
+        settle(&mut deps, "game", 1, &[]).unwrap();
+        assert_eq!(read_fees(&deps.storage, "uscrt").unwrap().accrued, Uint128(100));
+
+        // The house is below its previous high, no fee until the payout is won back
+        escrow(&mut deps, "game", 2, "player", 600);
+        settle(&mut deps, "game", 2, &[("player", 1200)]).unwrap();
+        escrow(&mut deps, "game", 3, "loser", 300);
+        settle(&mut deps, "game", 3, &[("loser", 0)]).unwrap();
+        assert_eq!(read_fees(&deps.storage, "uscrt").unwrap().accrued, Uint128(100));
+
+        escrow(&mut deps, "game", 4, "loser", 500);
+        settle(&mut deps, "game", 4, &[]).unwrap();
+        assert_eq!(read_fees(&deps.storage, "uscrt").unwrap().accrued, Uint128(120));
+
+        let msg = HandleMsg::WithdrawFees { denom: "uscrt".to_string(), amount: Uint128(50), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg);
+        assert!(res.is_err(), "Only the owner can withdraw fees");
+
+        let msg = HandleMsg::WithdrawFees { denom: "uscrt".to_string(), amount: Uint128(121), padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg);
+        assert!(res.is_err(), "Owner can't withdraw more than the accrued fees");
+
+        let msg = HandleMsg::WithdrawFees { denom: "uscrt".to_string(), amount: Uint128(50), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::GetFees { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetFees { fee_bps, accrued, withdrawn, available } => {
+                assert_eq!(fee_bps, 1000);
+                assert_eq!(accrued, Uint128(120));
+                assert_eq!(withdrawn, Uint128(50));
+                assert_eq!(available, Uint128(70));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_ownership_transfer() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        let msg = HandleMsg::ProposeOwner { new_owner: HumanAddr("typo".to_string()), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        let msg = HandleMsg::CancelOwnershipProposal { padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::AcceptOwnership { padding: None };
+        let res = handle(&mut deps, mock_env("typo", &[]), msg);
+        assert!(res.is_err(), "A cancelled proposal can't be accepted");
+
+        let msg = HandleMsg::ProposeOwner { new_owner: HumanAddr("new_owner".to_string()), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        assert_eq!(read_owner(&deps.storage).unwrap(), HumanAddr("owner".to_string()));
+
+        let msg = HandleMsg::AcceptOwnership { padding: None };
+        handle(&mut deps, mock_env("new_owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::SetOperator { operator: HumanAddr("operator".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg.clone());
+        assert!(res.is_err(), "The previous owner lost their permissions");
+        handle(&mut deps, mock_env("new_owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::SetFee { fee_bps: 500, padding: None };
+        let res = handle(&mut deps, mock_env("new_owner", &[]), msg.clone());
+        assert!(res.is_err(), "Only the operator can change the configuration");
+        handle(&mut deps, mock_env("operator", &[]), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::GetRoles {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetRoles { owner, operator, proposed_owner } => {
+                assert_eq!(owner, HumanAddr("new_owner".to_string()));
+                assert_eq!(operator, HumanAddr("operator".to_string()));
+                assert_eq!(proposed_owner, None);
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_game_registration() {
+        let mut deps = mock_dependencies(20, &coins(5000, "uscrt"));
+
+        let _init_res = init(&mut deps, mock_env("owner", &[]), init_msg()).unwrap();
+
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("game".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("attacker", &[]), msg.clone());
+        assert!(res.is_err(), "Only the game itself can register");
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("attacker".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("attacker", &[]), msg.clone());
+        assert!(res.is_err(), "Registration is allowed only once");
+        assert!(read_game(&deps.storage, &HumanAddr("attacker".to_string())).unwrap().is_none());
+
+        let res = settle(&mut deps, "attacker", 1, &[("attacker", 100)]);
+        assert!(res.is_err(), "Only registered games can pay");
+    }
+
+    #[test]
+    fn test_game_registry() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), InitMsg { fee_bps: Some(0), ..init_msg() });
+
+        let msg = HandleMsg::AddGame { address: HumanAddr("high_stakes".to_string()), exposure_limit: Some(Uint128(1000)), padding: None };
+        let res = handle(&mut deps, mock_env("operator", &[]), msg.clone());
+        assert!(res.is_err(), "Only the owner can add games");
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(1001), padding: None };
+        let res = handle(&mut deps, mock_env("high_stakes", &[]), msg);
+        assert!(res.is_err(), "Game can't reserve more than its exposure limit");
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(800), padding: None };
+        handle(&mut deps, mock_env("high_stakes", &[]), msg).unwrap();
+        escrow(&mut deps, "high_stakes", 1, "player", 400);
+        escrow(&mut deps, "game", 1, "loser", 300);
+
+        let msg = HandleMsg::DisableGame { address: HumanAddr("high_stakes".to_string()), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        let res = handle(&mut deps, mock_env("high_stakes", &[]), msg);
+        assert!(res.is_err(), "Disabled game can't take new bets");
+
+        let msg = HandleMsg::RemoveGame { address: HumanAddr("high_stakes".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg.clone());
+        assert!(res.is_err(), "Game with bets in play can't be removed");
+
+        // The disabled game still settles the bets in play
+        let msg = HandleMsg::ReleaseExposure { amount: Uint128(800), padding: None };
+        handle(&mut deps, mock_env("high_stakes", &[]), msg).unwrap();
+        settle(&mut deps, "high_stakes", 1, &[("player", 900)]).unwrap();
+        settle(&mut deps, "game", 1, &[("loser", 0)]).unwrap();
+
+        let res = query(&deps, QueryMsg::ListGames {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::ListGames { games } => {
+                assert_eq!(games.len(), 2);
+                assert_eq!(games[0].won, Uint128(300));
+                assert_eq!(games[1].paid, Uint128(500));
+                assert_eq!(games[1].reserved, Uint128(0));
+                assert!(!games[1].enabled);
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        handle(&mut deps, mock_env("owner", &[]), HandleMsg::RemoveGame { address: HumanAddr("high_stakes".to_string()), padding: None }).unwrap();
+        let res = settle(&mut deps, "high_stakes", 1, &[("player", 900)]);
+        assert!(res.is_err(), "Removed game can't use the bank");
+    }
+
+    #[test]
+    fn test_create_game() {
+        let res = init(&mut mock_dependencies(20, &[]), mock_env("owner", &[]), InitMsg { block_size: Some(0), ..init_msg() });
+        assert!(res.is_err(), "Responses can't be padded to a zero block size");
+        let res = init(&mut mock_dependencies(20, &[]), mock_env("owner", &[]), InitMsg { block_size: Some(MAX_BLOCK_SIZE + 1), ..init_msg() });
+        assert!(res.is_err(), "Block size is limited");
+
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        let rules = TableConfig { privacy_mode: true, ..TableConfig::default() };
+        let msg = HandleMsg::CreateGame { code_id: 1, code_hash: "".to_string(), rules: rules.clone(), exposure_limit: Some(Uint128(1000)), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only the owner can create games");
+
+        let bad_msg = HandleMsg::CreateGame { code_id: 1, code_hash: "".to_string(), rules: TableConfig { block_size: 0, ..rules.clone() }, exposure_limit: None, padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), bad_msg);
+        assert!(res.is_err(), "Responses can't be padded to a zero block size");
+
+        let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Instantiate { label, .. }) => assert!(label.ends_with("-1")),
+            _ => panic!("Expected the game to be instantiated"),
+        }
+
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("privacy_table".to_string()), padding: None };
+        handle(&mut deps, mock_env("privacy_table", &[]), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::ListGames {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::ListGames { games } => {
+                assert_eq!(games.len(), 2);
+                assert_eq!(games[0].rules, Some(TableConfig::default()));
+                assert_eq!(games[1].address, HumanAddr("privacy_table".to_string()));
+                assert_eq!(games[1].rules, Some(rules));
+                assert_eq!(games[1].exposure_limit, Some(Uint128(1000)));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_token_bankroll() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        let token = Token { address: HumanAddr("sscrt".to_string()), code_hash: "hash".to_string() };
+        let msg = HandleMsg::RegisterToken { address: token.address.clone(), code_hash: token.code_hash.clone(), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let rules = TableConfig { token: Some(token.clone()), ..TableConfig::default() };
+        let msg = HandleMsg::CreateGame { code_id: 1, code_hash: "".to_string(), rules, exposure_limit: None, padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("token_table".to_string()), padding: None };
+        handle(&mut deps, mock_env("token_table", &[]), msg).unwrap();
+
+        let fund = HandleMsg::Receive {
+            sender: HumanAddr("owner".to_string()),
+            from: HumanAddr("owner".to_string()),
+            amount: Uint128(1000),
+            msg: Some(to_binary(&ReceiveMsg::Deposit {}).unwrap()),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("fake_token", &[]), fund.clone());
+        assert!(res.is_err(), "Only registered tokens can call receive");
+        handle(&mut deps, mock_env("sscrt", &[]), fund).unwrap();
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(1001), padding: None };
+        let res = handle(&mut deps, mock_env("token_table", &[]), msg);
+        assert!(res.is_err(), "Token table is backed only by the token bankroll");
+
+        let msg = HandleMsg::Receive {
+            sender: HumanAddr("token_table".to_string()),
+            from: HumanAddr("token_table".to_string()),
+            amount: Uint128(400),
+            msg: Some(to_binary(&ReceiveMsg::EscrowStake { round_id: 1, player: HumanAddr("player".to_string()) }).unwrap()),
+            padding: None,
+        };
+        handle(&mut deps, mock_env("sscrt", &[]), msg).unwrap();
+
+        let res = settle(&mut deps, "token_table", 1, &[("player", 900)]).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => assert_eq!(contract_addr, &token.address),
+            _ => panic!("Expected a token transfer"),
+        }
+
+        let res = query(&deps, QueryMsg::GetBankBalance { denom: token.address.to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetBankBalance { total, .. } => assert_eq!(total, Uint128(500)),
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_denomination_pools() {
+        let mut deps = setup_bank(&[Coin::new(5000, "uscrt"), Coin::new(300, "uatom")], init_msg());
+
+        let rules = TableConfig { denom: "uatom".to_string(), ..TableConfig::default() };
+        let msg = HandleMsg::CreateGame { code_id: 1, code_hash: "".to_string(), rules, exposure_limit: None, padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        let msg = HandleMsg::UpdateGameAddress { address: HumanAddr("atom_table".to_string()), padding: None };
+        handle(&mut deps, mock_env("atom_table", &[]), msg).unwrap();
+
+        let msg = HandleMsg::SetDenomExposureLimit { denom: "uatom".to_string(), exposure_limit: Some(Uint128(200)), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(250), padding: None };
+        let res = handle(&mut deps, mock_env("atom_table", &[]), msg);
+        assert!(res.is_err(), "Reservations are capped by the denomination's exposure limit");
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(150), padding: None };
+        handle(&mut deps, mock_env("atom_table", &[]), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::GetBankBalance { denom: "uatom".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetBankBalance { free, reserved, total } => {
+                assert_eq!(free, Uint128(150));
+                assert_eq!(reserved, Uint128(150));
+                assert_eq!(total, Uint128(300));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let res = query(&deps, QueryMsg::GetBankBalance { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetBankBalance { reserved, .. } => assert_eq!(reserved, Uint128(0)),
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_ledger() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        escrow(&mut deps, "game", 3, "loser", 200);
+        settle(&mut deps, "game", 3, &[]).unwrap();
+        escrow(&mut deps, "game", 4, "player", 200);
+        settle(&mut deps, "game", 4, &[("player", 250)]).unwrap();
+        let msg = HandleMsg::WithdrawFees { denom: "uscrt".to_string(), amount: Uint128(20), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::SetViewingKey { key: "owner_key".to_string(), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        let msg = HandleMsg::SetViewingKey { key: "player_key".to_string(), padding: None };
+        handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetLedger { address: HumanAddr("player".to_string()), key: "player_key".to_string(), start_after: None, limit: None };
+        assert!(query(&deps, msg).is_err(), "Only the owner can read the ledger");
+
+        let msg = QueryMsg::GetLedger { address: HumanAddr("owner".to_string()), key: "owner_key".to_string(), start_after: None, limit: Some(2) };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::GetLedger { entries, total } => {
+                assert_eq!(total, 4);
+                assert_eq!(entries.len(), 2);
+                assert_eq!(entries[0].action, LedgerAction::Stake);
+                assert_eq!(entries[0].game, Some(HumanAddr("game".to_string())));
+                assert_eq!(entries[0].account, HumanAddr("loser".to_string()));
+                assert_eq!(entries[0].round, Some(3));
+                assert_eq!(entries[0].amount, Uint128(200));
+                assert_eq!(entries[1].action, LedgerAction::Stake);
+                assert_eq!(entries[1].round, Some(4));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let msg = QueryMsg::GetLedger { address: HumanAddr("owner".to_string()), key: "owner_key".to_string(), start_after: Some(1), limit: None };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::GetLedger { entries, .. } => {
+                assert_eq!(entries.len(), 2);
+                assert_eq!(entries[0].id, 2);
+                assert_eq!(entries[0].action, LedgerAction::Payout);
+                assert_eq!(entries[0].account, HumanAddr("player".to_string()));
+                assert_eq!(entries[0].amount, Uint128(250));
+                assert_eq!(entries[1].id, 3);
+                assert_eq!(entries[1].action, LedgerAction::Withdrawal);
+                assert_eq!(entries[1].account, HumanAddr("owner".to_string()));
+                assert_eq!(entries[1].amount, Uint128(20));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+        handle(&mut deps, mock_env("lp", &coins(500, "uscrt")), HandleMsg::Deposit { padding: None }).unwrap();
+
+        let limits = CircuitLimits {
+            max_payout: Some(Uint128(500)),
+            max_daily_game_payout: Some(Uint128(800)),
+            max_net_loss: Some(Uint128(1000)),
+            ..CircuitLimits::default()
+        };
+        let msg = HandleMsg::SetCircuitLimits { denom: "uscrt".to_string(), limits, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only the operator can set the circuit limits");
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        escrow(&mut deps, "game", 1, "player", 480);
+        escrow(&mut deps, "game", 2, "other_player", 100);
+
+        // A payout above the maximum trips the circuit and is held back
+        let res = settle(&mut deps, "game", 1, &[("player", 1080)]).unwrap();
+        assert!(res.messages.is_empty());
+
+        let res = settle(&mut deps, "game", 2, &[("other_player", 200)]).unwrap();
+        assert!(res.messages.is_empty(), "Payouts are held while the circuit is tripped");
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "New bets are paused while the circuit is tripped");
+
+        let res = query(&deps, QueryMsg::GetCircuit { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetCircuit { tripped, held_payouts, net_loss, .. } => {
+                assert!(tripped);
+                assert_eq!(held_payouts, 2);
+                assert_eq!(net_loss, Uint128(0));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().escrowed, Uint128(580), "Stakes of held payouts stay in escrow");
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().reserved, Uint128(700), "What the house owes to held payouts stays reserved");
+
+        let msg = HandleMsg::Redeem { denom: "uscrt".to_string(), shares: Uint128(4500), padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg);
+        assert!(res.is_err(), "LPs can't redeem funds owed to held payouts");
+
+        let msg = HandleMsg::ResetCircuit { pay_held: true, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only the operator can reset the circuit");
+        let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().reserved, Uint128(0));
+
+        // 700 were paid today, another 125 would be above the game's daily maximum
+        escrow(&mut deps, "game", 3, "player", 100);
+        let res = settle(&mut deps, "game", 3, &[("player", 225)]).unwrap();
+        assert!(res.messages.is_empty());
+
+        let msg = HandleMsg::ResetCircuit { pay_held: false, padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+        assert_eq!(res.messages, vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: HumanAddr("cosmos2contract".to_string()),
+            to_address: HumanAddr("player".to_string()),
+            amount: coins(100, "uscrt"),
+        })], "The stake of a dropped payout is paid back");
+        match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::ResetCircuit { paid, dropped } => {
+                assert_eq!(paid, 0);
+                assert_eq!(dropped, 1);
+            }
+            _ => panic!("Unexpected handle answer"),
+        }
+
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().escrowed, Uint128(0));
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn test_status() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        escrow(&mut deps, "game", 1, "player", 100);
+        escrow(&mut deps, "game", 2, "player", 100);
+
+        let msg = HandleMsg::SetStatus { status: ContractStatus::StopBets, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only the operator can change the status");
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "No new bets while bets are stopped");
+
+        let res = settle(&mut deps, "game", 1, &[("player", 200)]).unwrap();
+        assert_eq!(res.messages.len(), 1, "Bets in play still settle while bets are stopped");
+
+        let status = HandleMsg::SetStatus { status: ContractStatus::StopAll, padding: None };
+        handle(&mut deps, mock_env("owner", &[]), status).unwrap();
+        let res = settle(&mut deps, "game", 2, &[("player", 200)]);
+        assert!(res.is_err(), "Nothing is paid while the bank is stopped");
+
+        let res = query(&deps, QueryMsg::GetStatus {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetStatus { status } => assert_eq!(status, ContractStatus::StopAll),
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let status = HandleMsg::SetStatus { status: ContractStatus::Normal, padding: None };
+        handle(&mut deps, mock_env("owner", &[]), status).unwrap();
+        let msg = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn test_escrow() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), InitMsg { fee_bps: Some(0), ..init_msg() });
+
+        let msg = HandleMsg::EscrowStake { round_id: 1, player: HumanAddr("player".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("stranger", &coins(100, "uscrt")), msg.clone());
+        assert!(res.is_err(), "Only registered games can escrow stakes");
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "Stake should be sent along");
+
+        escrow(&mut deps, "game", 1, "kicked", 50);
+        escrow(&mut deps, "game", 2, "winner", 100);
+        escrow(&mut deps, "game", 2, "winner", 100);
+        escrow(&mut deps, "game", 2, "loser", 100);
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().escrowed, Uint128(350));
+        assert_eq!(read_game(&deps.storage, &HumanAddr("game".to_string())).unwrap().unwrap().open_rounds, vec![1, 2]);
+
+        // The winner's stake and profit are paid from the bank, the other stakes are won by the house
+        let res = settle(&mut deps, "game", 2, &[("winner", 400), ("loser", 0)]).unwrap();
+        assert_eq!(res.messages, vec![CosmosMsg::Bank(BankMsg::Send {
+            from_address: HumanAddr("cosmos2contract".to_string()),
+            to_address: HumanAddr("winner".to_string()),
+            amount: coins(400, "uscrt"),
+        })]);
+
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().escrowed, Uint128(0), "Stakes of older rounds are swept along");
+        let game = read_game(&deps.storage, &HumanAddr("game".to_string())).unwrap().unwrap();
+        assert!(game.open_rounds.is_empty());
+        assert_eq!(game.won, Uint128(150));
+        assert_eq!(game.paid, Uint128(200));
+    }
+
+    #[test]
+    fn test_round_settlement() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        escrow(&mut deps, "game", 1, "kicked", 50);
+        escrow(&mut deps, "game", 2, "winner", 100);
+        escrow(&mut deps, "game", 2, "loser", 100);
+
+        let res = settle(&mut deps, "game", 2, &[("stranger", 100)]);
+        assert!(res.is_err(), "Only players with a stake in the round can be paid");
+        let res = settle(&mut deps, "game", 2, &[("winner", 226)]);
+        assert!(res.is_err(), "Payout can't be above the maximum for the stake");
+        let res = settle(&mut deps, "game", 2, &[("winner", 100), ("winner", 100)]);
+        assert!(res.is_err(), "A stake can only be settled once");
+
+        let res = settle(&mut deps, "game", 2, &[("winner", 225), ("loser", 0)]).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        let res = settle(&mut deps, "game", 2, &[("winner", 225)]);
+        assert!(res.is_err(), "A round can only be settled once");
+        let res = settle(&mut deps, "game", 1, &[("kicked", 50)]);
+        assert!(res.is_err(), "Older rounds are closed when a later round is settled");
+
+        let msg = HandleMsg::SetViewingKey { key: "owner_key".to_string(), padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = QueryMsg::GetRoundRecord { address: HumanAddr("owner".to_string()), key: "owner_key".to_string(), game: HumanAddr("game".to_string()), round_id: 2 };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::GetRoundRecord { record } => {
+                let record = record.unwrap();
+                assert_eq!(record.stakes, Uint128(200));
+                assert_eq!(record.results.len(), 2);
+                assert_eq!(record.results[0].payout, Uint128(225));
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let msg = QueryMsg::GetRoundRecord { address: HumanAddr("owner".to_string()), key: "owner_key".to_string(), game: HumanAddr("game".to_string()), round_id: 1 };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::GetRoundRecord { record } => {
+                let record = record.unwrap();
+                assert_eq!(record.stakes, Uint128(50));
+                assert!(record.results.is_empty());
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_jackpot() {
+        let jackpot = JackpotConfig {
+            contribution: Uint128(100),
+            paytable: vec![JackpotPay { hand: JackpotHand::TripleSeven, pool_bps: 5000 }],
+        };
+        let game_config = TableConfig { jackpot: Some(jackpot), ..TableConfig::default() };
+        let mut deps = setup_bank(&coins(5000, "uscrt"), InitMsg { game_config: Some(game_config), ..init_msg() });
+
+        escrow(&mut deps, "game", 1, "player", 100);
+        let msg = HandleMsg::ContributeJackpot { round_id: 1, player: HumanAddr("player".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("attacker", &coins(100, "uscrt")), msg.clone());
+        assert!(res.is_err(), "Only games can contribute to the jackpot");
+        handle(&mut deps, mock_env("game", &coins(100, "uscrt")), msg).unwrap();
+
+        let res = query(&deps, QueryMsg::GetJackpot { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetJackpot { pool } => assert_eq!(pool, Uint128(100)),
+            _ => panic!("Unexpected query answer"),
+        }
+        let res = query(&deps, QueryMsg::GetPoolInfo { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetPoolInfo { pool_value, .. } => assert_eq!(pool_value, Uint128(4800), "Stakes and the jackpot aren't part of the pool"),
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let results = vec![Settlement { player: HumanAddr("player".to_string()), payout: Uint128(0), jackpot_bps: Some(10001) }];
+        let msg = HandleMsg::SettleRound { round_id: 1, results, padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "Jackpot share can't be more than the whole jackpot");
+
+        let results = vec![Settlement { player: HumanAddr("player".to_string()), payout: Uint128(0), jackpot_bps: Some(10000) }];
+        let msg = HandleMsg::SettleRound { round_id: 1, results, padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg);
+        assert!(res.is_err(), "Jackpot share should be on the table's paytable");
+
+        // A losing hand can still win the jackpot
+        let results = vec![Settlement { player: HumanAddr("player".to_string()), payout: Uint128(0), jackpot_bps: Some(5000) }];
+        let msg = HandleMsg::SettleRound { round_id: 1, results, padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount, .. }) => {
+                assert_eq!(to_address, &HumanAddr("player".to_string()));
+                assert_eq!(amount, &coins(50, "uscrt"));
+            }
+            _ => panic!("Expected a jackpot payment"),
+        }
+
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().jackpot, Uint128(50));
+    }
+
+    #[test]
+    fn test_emergency_withdrawal() {
+        let mut deps = mock_dependencies(20, &coins(5000, "uscrt"));
+        let res = init(&mut deps, mock_env("owner", &[]), InitMsg { emergency_delay: Some(60), ..init_msg() });
+        assert!(res.is_err(), "Emergency withdrawal delay is too short");
+
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+        escrow(&mut deps, "game", 1, "player", 100);
+
+        let msg = HandleMsg::SetEmergencyDelay { delay: MIN_EMERGENCY_DELAY, padding: None };
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::EmergencyWithdrawAll { padding: None };
+        let res = handle(&mut deps, mock_env("owner", &[]), msg.clone());
+        assert!(res.is_err(), "Emergency withdrawal should be announced first");
+
+        let announce = HandleMsg::AnnounceEmergencyWithdrawal { padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), announce.clone());
+        assert!(res.is_err(), "Only the owner can announce an emergency withdrawal");
+        let env = mock_env("owner", &[]);
+        let executable_at = env.block.time + MIN_EMERGENCY_DELAY;
+        handle(&mut deps, env.clone(), announce.clone()).unwrap();
+        let res = handle(&mut deps, env.clone(), announce);
+        assert!(res.is_err(), "Emergency withdrawal was already announced");
+
+        let res = query(&deps, QueryMsg::GetEmergencyWithdrawal {}).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetEmergencyWithdrawal { pending, delay } => {
+                assert_eq!(pending, Some(PendingWithdrawal { announced_at: env.block.time, executable_at }));
+                assert_eq!(delay, MIN_EMERGENCY_DELAY);
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let msg_reserve = HandleMsg::ReserveExposure { amount: Uint128(100), padding: None };
+        let res = handle(&mut deps, mock_env("game", &[]), msg_reserve);
+        assert!(res.is_err(), "New bets are paused once an emergency withdrawal is announced");
+
+        let res = handle(&mut deps, env.clone(), msg.clone());
+        assert!(res.is_err(), "Emergency withdrawal can't be executed before the delay passed");
+
+        let mut env = mock_env("owner", &[]);
+        env.block.time = executable_at;
+        let res = handle(&mut deps, env.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(read_emergency_withdrawal(&deps.storage).unwrap(), None);
+
+        let res = handle(&mut deps, env, HandleMsg::CancelEmergencyWithdrawal { padding: None });
+        assert!(res.is_err(), "Nothing left to cancel");
+
+        // The bookkeeping left behind isn't backed by any funds, the drained bank takes nothing more
+        assert_eq!(read_status(&deps.storage).unwrap(), ContractStatus::Drained);
+        let res = handle(&mut deps, mock_env("lp", &coins(500, "uscrt")), HandleMsg::Deposit { padding: None });
+        assert!(res.is_err(), "A drained bank takes no deposits");
+        let res = settle(&mut deps, "game", 1, &[("player", 200)]);
+        assert!(res.is_err(), "A drained bank settles no rounds");
+        let res = handle(&mut deps, mock_env("owner", &[]), HandleMsg::SetStatus { status: ContractStatus::Normal, padding: None });
+        assert!(res.is_err(), "The drained status can't be left");
+        let res = handle(&mut deps, mock_env("player", &[]), HandleMsg::CreateViewingKey { entropy: "entropy".to_string(), padding: None });
+        assert!(res.is_ok(), "Viewing keys still work to read the history");
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), init_msg());
+
+        escrow(&mut deps, "game", 1, "winner", 100);
+        escrow(&mut deps, "game", 1, "loser", 100);
+        settle(&mut deps, "game", 1, &[("winner", 225), ("loser", 0)]).unwrap();
+
+        let res = query(&deps, QueryMsg::GetStats { denom: "uscrt".to_string(), game: None, days: None }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetStats { total, daily } => {
+                assert_eq!(total.wagered, Uint128(200));
+                assert_eq!(total.paid_out, Uint128(225));
+                assert_eq!(total.hands, 2);
+                assert_eq!(total.house_profit, Uint128(0));
+                assert_eq!(total.house_loss, Uint128(25));
+                assert_eq!(total.house_edge_bps, -1250);
+                assert!(daily.is_empty());
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        // The stake of a player kicked from round 2 is forfeited when round 3 is settled
+        escrow(&mut deps, "game", 2, "kicked", 100);
+        escrow(&mut deps, "game", 3, "loser", 300);
+        settle(&mut deps, "game", 3, &[]).unwrap();
+
+        let res = query(&deps, QueryMsg::GetStats { denom: "uscrt".to_string(), game: Some(HumanAddr("game".to_string())), days: Some(7) }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetStats { total, daily } => {
+                assert_eq!(total.wagered, Uint128(600));
+                assert_eq!(total.hands, 4);
+                assert_eq!(total.biggest_payout, Uint128(225));
+                assert_eq!(total.house_profit, Uint128(375));
+                assert_eq!(total.house_edge_bps, 6250);
+                assert_eq!(daily.len(), 1);
+                assert_eq!(daily[0], total);
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let res = query(&deps, QueryMsg::GetStats { denom: "uatom".to_string(), game: None, days: Some(7) }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetStats { total, daily } => {
+                assert_eq!(total.hands, 0);
+                assert!(daily.is_empty());
+            }
+            _ => panic!("Unexpected query answer"),
+        }
+    }
+
+    #[test]
+    fn test_referral_rewards() {
+        let mut deps = setup_bank(&coins(5000, "uscrt"), InitMsg { fee_bps: Some(0), ..init_msg() });
+
+        let config = ReferralConfig { house_edge_bps: 200, share_bps: 5000 };
+        let msg = HandleMsg::SetReferralConfig { config: config.clone(), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Only the operator can set the referral config");
+        let res = handle(&mut deps, mock_env("owner", &[]), HandleMsg::SetReferralConfig { config: ReferralConfig { share_bps: 10001, ..config.clone() }, padding: None });
+        assert!(res.is_err(), "Referral share can't be more than 10000 basis points");
+        handle(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let register = |player: &str, referrer: &str| HandleMsg::RegisterReferral {
+            player: HumanAddr(player.to_string()),
+            referrer: HumanAddr(referrer.to_string()),
+            padding: None,
+        };
+        let res = handle(&mut deps, mock_env("player", &[]), register("player", "referrer"));
+        assert!(res.is_err(), "Only games can register referrals");
+        let res = handle(&mut deps, mock_env("game", &[]), register("player", "player"));
+        assert!(res.is_err(), "A player can't refer themselves");
+        handle(&mut deps, mock_env("game", &[]), register("player", "referrer")).unwrap();
+        handle(&mut deps, mock_env("game", &[]), register("player", "other")).unwrap();
+        assert_eq!(read_referrer(&deps.storage, &HumanAddr("player".to_string())).unwrap(), Some(HumanAddr("referrer".to_string())));
+
+        // The referrer earns half of the 2% house edge on the stake, win or lose
+        escrow(&mut deps, "game", 1, "player", 1000);
+        escrow(&mut deps, "game", 1, "stranger", 1000);
+        settle(&mut deps, "game", 1, &[("player", 0), ("stranger", 0)]).unwrap();
+
+        let res = query(&deps, QueryMsg::GetPoolInfo { denom: "uscrt".to_string() }).unwrap();
+        match from_binary(&res).unwrap() {
+            QueryAnswer::GetPoolInfo { pool_value, .. } => assert_eq!(pool_value, Uint128(4990), "Unclaimed rewards aren't part of the pool"),
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let msg = HandleMsg::SetViewingKey { key: "key".to_string(), padding: None };
+        handle(&mut deps, mock_env("referrer", &[]), msg).unwrap();
+        let msg = QueryMsg::GetReferralRewards { address: HumanAddr("referrer".to_string()), key: "key".to_string(), denom: "uscrt".to_string() };
+        match from_binary(&query(&deps, msg).unwrap()).unwrap() {
+            QueryAnswer::GetReferralRewards { rewards } => assert_eq!(rewards, ReferralRewards { accrued: Uint128(10), claimed: Uint128(0) }),
+            _ => panic!("Unexpected query answer"),
+        }
+
+        let msg = HandleMsg::ClaimReferralRewards { denom: "uscrt".to_string(), padding: None };
+        let res = handle(&mut deps, mock_env("referrer", &[]), msg.clone()).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount, .. }) => {
+                assert_eq!(to_address, &HumanAddr("referrer".to_string()));
+                assert_eq!(amount, &coins(10, "uscrt"));
+            }
+            _ => panic!("Expected a reward payment"),
+        }
+        assert_eq!(read_pool(&deps.storage, "uscrt").unwrap().referral_rewards, Uint128(0));
+
+        let res = handle(&mut deps, mock_env("referrer", &[]), msg);
+        assert!(res.is_err(), "Rewards were already claimed");
     }
 }
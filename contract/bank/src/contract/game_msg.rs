@@ -2,12 +2,92 @@ use cosmwasm_std::{HumanAddr, Uint128};
 use rs_poker::core::Card;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use crate::msg::{DEFAULT_BLOCK_SIZE, DEFAULT_DENOM};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub bank_address: HumanAddr,
     pub bank_code_hash: String,
     pub secret: u64,
+    pub config: Option<TableConfig>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct TableConfig {
+    pub block_size: u16,
+    pub privacy_mode: bool,
+    pub denom: String,
+    pub token: Option<Token>,
+    pub jackpot: Option<JackpotConfig>,
+    pub min_bet: Uint128,
+    pub max_bet: Option<Uint128>,
+    pub bet_step: Uint128,
+    pub bet_policy: BetPolicy,
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        TableConfig {
+            block_size: DEFAULT_BLOCK_SIZE,
+            privacy_mode: false,
+            denom: DEFAULT_DENOM.to_string(),
+            token: None,
+            jackpot: None,
+            min_bet: Uint128(1),
+            max_bet: None,
+            bet_step: Uint128(1),
+            bet_policy: BetPolicy::FreeBankroll { bps: 10000 },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BetPolicy {
+    Fixed,
+    FreeBankroll {
+        bps: u16,
+    },
+    Kelly {
+        house_edge_bps: u16,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotConfig {
+    pub contribution: Uint128,
+    pub paytable: Vec<JackpotPay>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotPay {
+    pub hand: JackpotHand,
+    pub pool_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JackpotHand {
+    TripleSeven,
+    SuitedNatural {
+        suit: Option<CardSuit>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSuit {
+    Spade,
+    Club,
+    Heart,
+    Diamond,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Token {
+    pub address: HumanAddr,
+    pub code_hash: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -85,7 +165,6 @@ pub enum HandleMsg {
         seat: u8,
     },
     Kick {
-        target: HumanAddr,
         seat: u8,
     },
     Hit {
@@ -0,0 +1,18 @@
+use cosmwasm_std::{HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The SNIP-20 messages the bank sends to the tokens it holds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    Transfer {
+        recipient: HumanAddr,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+}
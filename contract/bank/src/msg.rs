@@ -1,12 +1,275 @@
-use cosmwasm_std::{HumanAddr, Uint128};
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use crate::contract::game_msg::{TableConfig, Token};
+
+pub const DEFAULT_BLOCK_SIZE: u16 = 256;
+pub const MAX_BLOCK_SIZE: u16 = 4096;
+pub const DEFAULT_FEE_BPS: u16 = 1000;
+pub const MAX_FEE_BPS: u16 = 10000;
+pub const DEFAULT_DENOM: &str = "uscrt";
+pub const MAX_LEDGER_PAGE: u32 = 100;
+pub const MAX_STATS_DAYS: u32 = 90;
+pub const DEFAULT_NET_LOSS_WINDOW: u64 = 24 * 60 * 60;
+pub const DEFAULT_EMERGENCY_DELAY: u64 = 2 * 24 * 60 * 60;
+pub const MIN_EMERGENCY_DELAY: u64 = 24 * 60 * 60;
+/// Most a player can get back for a stake, in basis points of it. A blackjack returns the stake and 1.25 times it
+pub const MAX_PAYOUT_BPS: u128 = 22500;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub game_contract_code_id: u64,
     pub game_contract_code_hash: String,
     pub secret : u64,
+    pub block_size: Option<u16>,
+    pub game_config: Option<TableConfig>,
+    /// Owner's fee on net house winnings, in basis points
+    pub fee_bps: Option<u16>,
+    /// Seconds between announcing an emergency withdrawal and executing it
+    pub emergency_delay: Option<u64>,
+}
+
+/// Liquidity provided to the house in one denomination, owned by LPs in proportion to their shares.
+/// A denomination is either a native coin denom or the address of a registered SNIP-20 token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Pool {
+    pub total_shares: Uint128,
+    /// Funds set aside for the maximum payout of bets that are still in play
+    pub reserved: Uint128,
+    /// Cap on the funds reserved at once, across all the games betting in this denomination
+    pub exposure_limit: Option<Uint128>,
+    /// Stakes of the rounds that weren't settled yet, they aren't part of the pool
+    pub escrowed: Uint128,
+    /// Progressive jackpot funded by the contributions of every game betting in this denomination, it isn't part of the pool
+    pub jackpot: Uint128,
+    /// Referral rewards accrued and not claimed yet, they aren't part of the pool
+    pub referral_rewards: Uint128,
+}
+
+/// A game contract allowed to use the bankroll, with its own exposure limit and P&L
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GameInfo {
+    pub address: HumanAddr,
+    /// A disabled game can't take new bets, but can still settle the bets in play
+    pub enabled: bool,
+    /// Known only for games the bank instantiated itself
+    pub rules: Option<TableConfig>,
+    pub exposure_limit: Option<Uint128>,
+    pub reserved: Uint128,
+    pub won: Uint128,
+    pub paid: Uint128,
+    /// Day, counted from the epoch, `paid_today` was paid in
+    pub payout_day: u64,
+    pub paid_today: Uint128,
+    /// Rounds with stakes in escrow, oldest first
+    pub open_rounds: Vec<u64>,
+}
+
+impl GameInfo {
+    pub fn new(address: HumanAddr, rules: Option<TableConfig>, exposure_limit: Option<Uint128>) -> GameInfo {
+        GameInfo {
+            address,
+            enabled: true,
+            rules,
+            exposure_limit,
+            reserved: Uint128::from(0_u128),
+            won: Uint128::from(0_u128),
+            paid: Uint128::from(0_u128),
+            payout_day: 0,
+            paid_today: Uint128::from(0_u128),
+            open_rounds: vec![],
+        }
+    }
+}
+
+/// A registered SNIP-20 token. The bank can't see its token balances, so it keeps track of them itself
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenPool {
+    pub token: Token,
+    pub balance: Uint128,
+}
+
+/// Owner fees, taken from the house's net winnings (stakes collected minus payouts).
+/// A fee is only charged when the net winnings reach a new high, so losses have to be won back first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct FeeLedger {
+    pub house_won: Uint128,
+    pub house_paid: Uint128,
+    pub net_high_water: Uint128,
+    /// All the fees accrued since initialization, withdrawn or not
+    pub accrued: Uint128,
+    pub withdrawn: Uint128,
+}
+
+/// Limits of a denomination that trip the circuit breaker, a limit that isn't set is never crossed
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitLimits {
+    pub max_payout: Option<Uint128>,
+    pub max_daily_game_payout: Option<Uint128>,
+    pub max_net_loss: Option<Uint128>,
+    /// Length, in seconds, of the rolling window the net loss is measured over
+    pub net_loss_window: u64,
+}
+
+impl Default for CircuitLimits {
+    fn default() -> Self {
+        CircuitLimits {
+            max_payout: None,
+            max_daily_game_payout: None,
+            max_net_loss: None,
+            net_loss_window: DEFAULT_NET_LOSS_WINDOW,
+        }
+    }
+}
+
+/// Stakes collected and payouts made in one hour
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LossBucket {
+    pub hour: u64,
+    pub won: Uint128,
+    pub paid: Uint128,
+}
+
+/// Hourly results of a denomination, only the buckets within the net loss window are kept
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct LossWindow {
+    pub buckets: Vec<LossBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Stake {
+    pub player: HumanAddr,
+    pub amount: Uint128,
+}
+
+/// Stakes a game placed in escrow for one of its rounds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct RoundEscrow {
+    pub stakes: Vec<Stake>,
+}
+
+/// What a player gets back when a round is settled, the stake included. Losers get nothing
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Settlement {
+    pub player: HumanAddr,
+    pub payout: Uint128,
+    /// Share of the jackpot pool the player won, in basis points, paid on top of the payout
+    pub jackpot_bps: Option<u16>,
+}
+
+/// Audit record of a settled round. Rounds that ended without a roundup are recorded with no results
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundRecord {
+    pub round_id: u64,
+    pub settled_at: u64,
+    /// Sum of the stakes that were in escrow for the round
+    pub stakes: Uint128,
+    pub results: Vec<Settlement>,
+}
+
+/// A payout of a settled round, held back when it was asked for while the circuit was tripped, or when it tripped it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HeldPayout {
+    pub game: HumanAddr,
+    pub to: HumanAddr,
+    pub denom: String,
+    pub amount: Uint128,
+    /// Part of the amount that is the player's own stake, coming out of escrow. The rest is paid by the house
+    pub stake: Uint128,
+    pub round: u64,
+}
+
+/// Referrers earn `share_bps` of the house edge on each settled stake of the players they referred.
+/// Rewards are off while either is zero
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReferralConfig {
+    /// Expected house edge on a stake, in basis points
+    pub house_edge_bps: u16,
+    /// Share of the house edge paid to the referrer, in basis points
+    pub share_bps: u16,
+}
+
+/// Rewards of a referrer in one denomination
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReferralRewards {
+    pub accrued: Uint128,
+    pub claimed: Uint128,
+}
+
+/// Emergency withdrawal announced by the owner. No new bets are taken until it is executed or cancelled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingWithdrawal {
+    pub announced_at: u64,
+    pub executable_at: u64,
+}
+
+/// While tripped, payouts are held back and no new bets are taken until the operator resets the circuit
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Circuit {
+    pub tripped: bool,
+    pub reason: Option<String>,
+    pub tripped_at: Option<u64>,
+    pub held: Vec<HeldPayout>,
+}
+
+/// Running totals of a denomination or a game, overall or for one day. Stakes count once their round is
+/// settled and payouts once they are paid, so a held payout counts on the day the circuit is reset
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct Stats {
+    /// Day, counted from the epoch, of the latest settlement counted
+    pub day: u64,
+    pub wagered: Uint128,
+    /// Payouts made, the stakes given back included
+    pub paid_out: Uint128,
+    pub hands: u64,
+    pub biggest_payout: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StatsView {
+    pub day: u64,
+    pub wagered: Uint128,
+    pub paid_out: Uint128,
+    pub hands: u64,
+    pub biggest_payout: Uint128,
+    /// Net result of the house, only one of the profit and the loss isn't zero
+    pub house_profit: Uint128,
+    pub house_loss: Uint128,
+    /// Share of the wagers the house kept, in basis points, negative when the house lost
+    pub house_edge_bps: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerAction {
+    /// Stake and winnings paid back to a player
+    Payout,
+    /// Stake placed in escrow when a player bids
+    Stake,
+    Deposit,
+    /// Jackpot contribution of a player that bid for the jackpot
+    Contribution,
+    /// Share of the jackpot paid to a player
+    Jackpot,
+    /// Referral rewards claimed by a referrer
+    ReferralReward,
+    /// Redeemed shares, withdrawn fees and emergency withdrawals
+    Withdrawal,
+}
+
+/// A movement of bank funds, entries are only ever appended
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LedgerEntry {
+    pub id: u64,
+    pub action: LedgerAction,
+    /// Game that asked for the payout or sent the stakes
+    pub game: Option<HumanAddr>,
+    /// Player, LP or owner on the other side of the movement
+    pub account: HumanAddr,
+    pub round: Option<u64>,
+    pub denom: String,
+    pub amount: Uint128,
+    pub time: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -14,28 +277,331 @@ pub struct InitMsg {
 pub enum HandleMsg {
     UpdateGameAddress {
         address: HumanAddr,
+        padding: Option<String>,
     },
-    PayToWinner {
-        amount: Uint128,
-        to: HumanAddr,
+    /// Pauses new bets and starts the delay after which the owner can withdraw everything
+    AnnounceEmergencyWithdrawal {
+        padding: Option<String>,
+    },
+    CancelEmergencyWithdrawal {
+        padding: Option<String>,
+    },
+    /// Dooms day command, withdraws the whole bankroll to the owner once the announced delay passed
+    EmergencyWithdrawAll {
+        padding: Option<String>,
+    },
+    /// Applies to withdrawals announced later, it can't be below `MIN_EMERGENCY_DELAY`
+    SetEmergencyDelay {
+        delay: u64,
+        padding: Option<String>,
     },
-    EmergencyWithdrawAll {}, // Dooms day command, will withdraw to an hard-coded address.
-    ChangeOwner {
+    ProposeOwner {
         new_owner: HumanAddr,
+        padding: Option<String>,
+    },
+    AcceptOwnership {
+        padding: Option<String>,
+    },
+    CancelOwnershipProposal {
+        padding: Option<String>,
+    },
+    SetOperator {
+        operator: HumanAddr,
+        padding: Option<String>,
+    },
+    SetStatus {
+        status: ContractStatus,
+        padding: Option<String>,
+    },
+    /// Escrows the stake sent along for a bet of the player, token tables use a `ReceiveMsg::EscrowStake`
+    EscrowStake {
+        round_id: u64,
+        player: HumanAddr,
+        padding: Option<String>,
+    },
+    /// Adds the contribution sent along to the jackpot of the game's denomination, token tables use a `ReceiveMsg::ContributeJackpot`
+    ContributeJackpot {
+        round_id: u64,
+        player: HumanAddr,
+        padding: Option<String>,
+    },
+    /// Sets the player's referrer, a player keeps the first referrer registered by any game
+    RegisterReferral {
+        player: HumanAddr,
+        referrer: HumanAddr,
+        padding: Option<String>,
+    },
+    /// Pays the sender the referral rewards accrued in a denomination
+    ClaimReferralRewards {
+        denom: String,
+        padding: Option<String>,
+    },
+    SetReferralConfig {
+        config: ReferralConfig,
+        padding: Option<String>,
+    },
+    /// Pays the players of a round out of its escrow and the house pool. Stakes of players left out are won by the house
+    SettleRound {
+        round_id: u64,
+        results: Vec<Settlement>,
+        padding: Option<String>,
+    },
+    ReserveExposure {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    ReleaseExposure {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    WithdrawFees {
+        denom: String,
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    CreateGame {
+        code_id: u64,
+        code_hash: String,
+        rules: TableConfig,
+        exposure_limit: Option<Uint128>,
+        padding: Option<String>,
+    },
+    RegisterToken {
+        address: HumanAddr,
+        code_hash: String,
+        padding: Option<String>,
+    },
+    /// Called by a registered SNIP-20 token when it's sent to the bank, `msg` holds a `ReceiveMsg`
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    AddGame {
+        address: HumanAddr,
+        exposure_limit: Option<Uint128>,
+        padding: Option<String>,
+    },
+    RemoveGame {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    DisableGame {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    EnableGame {
+        address: HumanAddr,
+        padding: Option<String>,
+    },
+    SetGameExposureLimit {
+        address: HumanAddr,
+        exposure_limit: Option<Uint128>,
+        padding: Option<String>,
+    },
+    SetFee {
+        fee_bps: u16,
+        padding: Option<String>,
+    },
+    SetCircuitLimits {
+        denom: String,
+        limits: CircuitLimits,
+        padding: Option<String>,
+    },
+    /// Closes the circuit, held payouts are paid when `pay_held` is set and dropped otherwise
+    ResetCircuit {
+        pay_held: bool,
+        padding: Option<String>,
+    },
+    SetDenomExposureLimit {
+        denom: String,
+        exposure_limit: Option<Uint128>,
+        padding: Option<String>,
+    },
+    /// Deposits the single native coin sent, tokens are deposited with a `ReceiveMsg::Deposit`
+    Deposit {
+        padding: Option<String>,
+    },
+    Redeem {
+        denom: String,
+        shares: Uint128,
+        padding: Option<String>,
+    },
+    CreateViewingKey {
+        entropy: String,
+        padding: Option<String>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
     },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    GetBankBalance {},
+    GetBankBalance {
+        denom: String,
+    },
+    GetPoolInfo {
+        denom: String,
+    },
+    GetFees {
+        denom: String,
+    },
+    GetRoles {},
+    GetStatus {},
+    GetJackpot {
+        denom: String,
+    },
+    GetEmergencyWithdrawal {},
+    GetReferralConfig {},
+    /// Rewards a referrer accrued in a denomination, only the referrer can read them
+    GetReferralRewards {
+        address: HumanAddr,
+        key: String,
+        denom: String,
+    },
+    /// Stats of the game when it is set, of every game betting in the denomination otherwise.
+    /// Daily stats cover up to `days` days ending on the day of the latest settlement, newest first
+    GetStats {
+        denom: String,
+        game: Option<HumanAddr>,
+        days: Option<u32>,
+    },
+    /// State of the circuit breaker and the limits of a denomination
+    GetCircuit {
+        denom: String,
+    },
+    ListGames {},
+    GetLpPosition {
+        address: HumanAddr,
+        key: String,
+        denom: String,
+    },
+    /// Ledger entries in the order they were recorded, only the owner can read the ledger
+    GetLedger {
+        address: HumanAddr,
+        key: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Settlement of a game's round, only the owner can read it
+    GetRoundRecord {
+        address: HumanAddr,
+        key: String,
+        game: HumanAddr,
+        round_id: u64,
+    },
+}
+
+impl QueryMsg {
+    /// Address and viewing key of queries that expose per-account data
+    pub fn get_validation_params(&self) -> (&HumanAddr, &str) {
+        match self {
+            QueryMsg::GetLpPosition { address, key, .. } => (address, key.as_str()),
+            QueryMsg::GetLedger { address, key, .. } => (address, key.as_str()),
+            QueryMsg::GetReferralRewards { address, key, .. } => (address, key.as_str()),
+            QueryMsg::GetRoundRecord { address, key, .. } => (address, key.as_str()),
+            _ => panic!("This query type does not require authentication"),
+        }
+    }
+}
+
+/// Sent as the `msg` of a SNIP-20 `Send` to the bank
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Stake of a token table's player
+    EscrowStake {
+        round_id: u64,
+        player: HumanAddr,
+    },
+    /// Jackpot contribution of a token table's player
+    ContributeJackpot {
+        round_id: u64,
+        player: HumanAddr,
+    },
+    /// LP deposit into the token's pool
+    Deposit {},
 }
 
 /// Responses from handle function
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
-    ChangeOwner {},
+    ProposeOwner {},
+    AcceptOwnership {},
+    CancelOwnershipProposal {},
+    SetOperator {},
+    AnnounceEmergencyWithdrawal {
+        executable_at: u64,
+    },
+    CancelEmergencyWithdrawal {},
+    SetEmergencyDelay {
+        delay: u64,
+    },
+    ClaimReferralRewards {
+        amount: Uint128,
+    },
+    SetReferralConfig {
+        status: ResponseStatus,
+    },
+    SetStatus {
+        status: ResponseStatus,
+    },
+    WithdrawFees {
+        amount: Uint128,
+    },
+    SetFee {
+        fee_bps: u16,
+    },
+    SetCircuitLimits {
+        status: ResponseStatus,
+    },
+    ResetCircuit {
+        paid: u32,
+        dropped: u32,
+    },
+    SetDenomExposureLimit {
+        status: ResponseStatus,
+    },
+    UpdateGame {
+        status: ResponseStatus,
+    },
+    Deposit {
+        shares: Uint128,
+    },
+    Redeem {
+        amount: Uint128,
+    },
+    CreateViewingKey {
+        key: String,
+    },
+    SetViewingKey {
+        status: ResponseStatus,
+    },
+}
+
+/// In `StopBets` games can't reserve exposure for new bets but the bets in play still settle.
+/// `StopAll` leaves only the owner's emergency withdrawal, viewing keys and changing the status back.
+/// `Drained` is set by the emergency withdrawal and can't be left, only viewing keys still work
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatus {
+    Normal,
+    StopBets,
+    StopAll,
+    Drained,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    Success,
+    Failure,
 }
 
 // We define a custom struct for each query response
@@ -43,6 +609,70 @@ pub enum HandleAnswer {
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
     GetBankBalance {
-        balance: Uint128,
+        free: Uint128,
+        reserved: Uint128,
+        total: Uint128,
+    },
+    GetPoolInfo {
+        total_shares: Uint128,
+        pool_value: Uint128,
+        share_price: Decimal,
+    },
+    GetFees {
+        fee_bps: u16,
+        accrued: Uint128,
+        withdrawn: Uint128,
+        available: Uint128,
+    },
+    GetRoles {
+        owner: HumanAddr,
+        operator: HumanAddr,
+        proposed_owner: Option<HumanAddr>,
+    },
+    GetStatus {
+        status: ContractStatus,
+    },
+    GetJackpot {
+        pool: Uint128,
+    },
+    GetEmergencyWithdrawal {
+        pending: Option<PendingWithdrawal>,
+        delay: u64,
+    },
+    GetReferralConfig {
+        config: ReferralConfig,
+    },
+    GetReferralRewards {
+        rewards: ReferralRewards,
+    },
+    GetStats {
+        total: StatsView,
+        daily: Vec<StatsView>,
+    },
+    GetCircuit {
+        tripped: bool,
+        reason: Option<String>,
+        tripped_at: Option<u64>,
+        held_payouts: u32,
+        limits: CircuitLimits,
+        /// Net loss over the window ending at the last payout or stake collected in the denomination
+        net_loss: Uint128,
+    },
+    ListGames {
+        games: Vec<GameInfo>,
+    },
+    GetLpPosition {
+        shares: Uint128,
+        value: Uint128,
+    },
+    GetLedger {
+        entries: Vec<LedgerEntry>,
+        total: u64,
+    },
+    GetRoundRecord {
+        record: Option<RoundRecord>,
+    },
+    ViewingKeyError {
+        msg: String,
     },
 }
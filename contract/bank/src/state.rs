@@ -1,14 +1,29 @@
 use std::any::type_name;
 
-use cosmwasm_std::{HumanAddr, StdResult, Storage, ReadonlyStorage, StdError};
+use cosmwasm_std::{HumanAddr, StdResult, Storage, ReadonlyStorage, StdError, Uint128};
 use cosmwasm_storage::{ReadonlySingleton, Singleton};
 use serde::{de::DeserializeOwned, Serialize};
 use secret_toolkit::serialization::{Bincode2, Serde};
+use crate::msg::{Circuit, CircuitLimits, ContractStatus, FeeLedger, GameInfo, LedgerEntry, LossWindow, PendingWithdrawal, Pool, ReferralConfig, ReferralRewards, RoundEscrow, RoundRecord, Stats, TokenPool};
 
 static KEY_OWNER: &[u8] = b"owner";
+static KEY_PROPOSED_OWNER: &[u8] = b"proposedowner";
+static KEY_OPERATOR: &[u8] = b"operator";
 static PENDING_GAME_ADDRESS: &[u8] = b"pending";
-static KEY_GAME_ADDRESS: &[u8] = b"gameaddress";
+static KEY_GAMES: &[u8] = b"games";
+static KEY_TOKENS: &[u8] = b"tokens";
 static KEY_BANK_ADDRESS: &[u8] = b"bankaddress";
+static KEY_BLOCK_SIZE: &[u8] = b"blocksize";
+static KEY_FEE_BPS: &[u8] = b"feebps";
+static KEY_SECRET: &[u8] = b"secret";
+static KEY_GAMES_CREATED: &[u8] = b"gamescreated";
+static KEY_PENDING_GAME: &[u8] = b"pendinggame";
+static KEY_LEDGER_LEN: &[u8] = b"ledgerlen";
+static KEY_CIRCUIT: &[u8] = b"circuit";
+static KEY_STATUS: &[u8] = b"status";
+static KEY_EMERGENCY_DELAY: &[u8] = b"emergencydelay";
+static KEY_EMERGENCY_WITHDRAWAL: &[u8] = b"emergencywithdrawal";
+static KEY_REFERRAL_CONFIG: &[u8] = b"referralconfig";
 
 pub fn set_pending_game_address<S: Storage>(storage: &mut S) -> StdResult<()> {
     let marker = "True".to_string();
@@ -27,6 +42,34 @@ pub fn is_pending_game_address<S: Storage>(storage: &S) -> StdResult<bool> {
     Ok(is_pending.eq("True"))
 }
 
+/// Registry entry of the game that is being instantiated, its address is filled in when it registers
+pub fn store_pending_game<S: Storage>(storage: &mut S, data: &GameInfo) -> StdResult<()> {
+    Singleton::new(storage, KEY_PENDING_GAME).save(data)?;
+    Ok(())
+}
+
+pub fn read_pending_game<S: Storage>(storage: &S) -> StdResult<GameInfo> {
+    ReadonlySingleton::new(storage, KEY_PENDING_GAME).load()
+}
+
+pub fn store_secret<S: Storage>(storage: &mut S, data: &u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_SECRET).save(data)?;
+    Ok(())
+}
+
+pub fn read_secret<S: Storage>(storage: &S) -> StdResult<u64> {
+    ReadonlySingleton::new(storage, KEY_SECRET).load()
+}
+
+pub fn store_games_created<S: Storage>(storage: &mut S, data: &u32) -> StdResult<()> {
+    Singleton::new(storage, KEY_GAMES_CREATED).save(data)?;
+    Ok(())
+}
+
+pub fn read_games_created<S: Storage>(storage: &S) -> StdResult<u32> {
+    ReadonlySingleton::new(storage, KEY_GAMES_CREATED).load()
+}
+
 pub fn store_owner<S: Storage>(storage: &mut S, data: &HumanAddr) -> StdResult<()> {
     Singleton::new(storage, KEY_OWNER).save(data)?;
     Ok(())
@@ -36,13 +79,65 @@ pub fn read_owner<S: Storage>(storage: &S) -> StdResult<HumanAddr> {
     ReadonlySingleton::new(storage, KEY_OWNER).load()
 }
 
-pub fn store_game_address<S: Storage>(storage: &mut S, data: &HumanAddr) -> StdResult<()> {
-    Singleton::new(storage, KEY_GAME_ADDRESS).save(data)?;
+pub fn store_proposed_owner<S: Storage>(storage: &mut S, data: &Option<HumanAddr>) -> StdResult<()> {
+    Singleton::new(storage, KEY_PROPOSED_OWNER).save(data)?;
     Ok(())
 }
 
-pub fn read_game_address<S: Storage>(storage: &S) -> StdResult<HumanAddr> {
-    ReadonlySingleton::new(storage, KEY_GAME_ADDRESS).load()
+pub fn read_proposed_owner<S: Storage>(storage: &S) -> StdResult<Option<HumanAddr>> {
+    ReadonlySingleton::new(storage, KEY_PROPOSED_OWNER).load()
+}
+
+pub fn store_operator<S: Storage>(storage: &mut S, data: &HumanAddr) -> StdResult<()> {
+    Singleton::new(storage, KEY_OPERATOR).save(data)?;
+    Ok(())
+}
+
+pub fn read_operator<S: Storage>(storage: &S) -> StdResult<HumanAddr> {
+    ReadonlySingleton::new(storage, KEY_OPERATOR).load()
+}
+
+pub fn store_game_list<S: Storage>(storage: &mut S, data: &[HumanAddr]) -> StdResult<()> {
+    Singleton::new(storage, KEY_GAMES).save(&data.to_vec())?;
+    Ok(())
+}
+
+pub fn read_game_list<S: Storage>(storage: &S) -> StdResult<Vec<HumanAddr>> {
+    ReadonlySingleton::new(storage, KEY_GAMES).load()
+}
+
+pub fn store_token_list<S: Storage>(storage: &mut S, data: &[HumanAddr]) -> StdResult<()> {
+    Singleton::new(storage, KEY_TOKENS).save(&data.to_vec())?;
+    Ok(())
+}
+
+pub fn read_token_list<S: Storage>(storage: &S) -> StdResult<Vec<HumanAddr>> {
+    ReadonlySingleton::new(storage, KEY_TOKENS).load()
+}
+
+pub fn store_token_pool<S: Storage>(storage: &mut S, pool: &TokenPool) -> StdResult<()> {
+    let key = "token".to_string() + pool.token.address.as_str();
+    save(storage, key.as_bytes(), pool)
+}
+
+pub fn read_token_pool<S: Storage>(storage: &S, address: &HumanAddr) -> StdResult<Option<TokenPool>> {
+    let key = "token".to_string() + address.as_str();
+    may_load(storage, key.as_bytes())
+}
+
+pub fn store_game<S: Storage>(storage: &mut S, game: &GameInfo) -> StdResult<()> {
+    let key = "game".to_string() + game.address.as_str();
+    save(storage, key.as_bytes(), game)
+}
+
+pub fn read_game<S: Storage>(storage: &S, address: &HumanAddr) -> StdResult<Option<GameInfo>> {
+    let key = "game".to_string() + address.as_str();
+    may_load(storage, key.as_bytes())
+}
+
+pub fn remove_game<S: Storage>(storage: &mut S, address: &HumanAddr) {
+    let key = "game".to_string() + address.as_str();
+    storage.remove(key.as_bytes());
 }
 
 pub fn store_bank_address<S: Storage>(storage: &mut S, data: &HumanAddr) -> StdResult<()> {
@@ -54,6 +149,207 @@ pub fn read_bank_address<S: Storage>(storage: &S) -> StdResult<HumanAddr> {
     ReadonlySingleton::new(storage, KEY_BANK_ADDRESS).load()
 }
 
+pub fn store_block_size<S: Storage>(storage: &mut S, data: &u16) -> StdResult<()> {
+    Singleton::new(storage, KEY_BLOCK_SIZE).save(data)?;
+    Ok(())
+}
+
+pub fn read_block_size<S: Storage>(storage: &S) -> StdResult<u16> {
+    ReadonlySingleton::new(storage, KEY_BLOCK_SIZE).load()
+}
+
+pub fn store_pool<S: Storage>(storage: &mut S, denom: &str, data: &Pool) -> StdResult<()> {
+    let key = "pool".to_string() + denom;
+    save(storage, key.as_bytes(), data)
+}
+
+/// Pools come to exist with their first deposit or bet, until then they are empty
+pub fn read_pool<S: Storage>(storage: &S, denom: &str) -> StdResult<Pool> {
+    let key = "pool".to_string() + denom;
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_fee_bps<S: Storage>(storage: &mut S, data: &u16) -> StdResult<()> {
+    Singleton::new(storage, KEY_FEE_BPS).save(data)?;
+    Ok(())
+}
+
+pub fn read_fee_bps<S: Storage>(storage: &S) -> StdResult<u16> {
+    ReadonlySingleton::new(storage, KEY_FEE_BPS).load()
+}
+
+pub fn store_fees<S: Storage>(storage: &mut S, denom: &str, data: &FeeLedger) -> StdResult<()> {
+    let key = "fees".to_string() + denom;
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_fees<S: Storage>(storage: &S, denom: &str) -> StdResult<FeeLedger> {
+    let key = "fees".to_string() + denom;
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_lp_shares<S: Storage>(storage: &mut S, denom: &str, address: &HumanAddr, shares: &Uint128) -> StdResult<()> {
+    let key = "shares".to_string() + denom + "/" + address.as_str();
+    save(storage, key.as_bytes(), shares)
+}
+
+pub fn read_lp_shares<S: Storage>(storage: &S, denom: &str, address: &HumanAddr) -> StdResult<Uint128> {
+    let key = "shares".to_string() + denom + "/" + address.as_str();
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_status<S: Storage>(storage: &mut S, data: &ContractStatus) -> StdResult<()> {
+    Singleton::new(storage, KEY_STATUS).save(data)?;
+    Ok(())
+}
+
+pub fn read_status<S: Storage>(storage: &S) -> StdResult<ContractStatus> {
+    ReadonlySingleton::new(storage, KEY_STATUS).load()
+}
+
+pub fn store_emergency_delay<S: Storage>(storage: &mut S, data: &u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_EMERGENCY_DELAY).save(data)?;
+    Ok(())
+}
+
+pub fn read_emergency_delay<S: Storage>(storage: &S) -> StdResult<u64> {
+    ReadonlySingleton::new(storage, KEY_EMERGENCY_DELAY).load()
+}
+
+pub fn store_emergency_withdrawal<S: Storage>(storage: &mut S, data: &Option<PendingWithdrawal>) -> StdResult<()> {
+    Singleton::new(storage, KEY_EMERGENCY_WITHDRAWAL).save(data)?;
+    Ok(())
+}
+
+pub fn read_emergency_withdrawal<S: Storage>(storage: &S) -> StdResult<Option<PendingWithdrawal>> {
+    ReadonlySingleton::new(storage, KEY_EMERGENCY_WITHDRAWAL).load()
+}
+
+pub fn store_circuit<S: Storage>(storage: &mut S, data: &Circuit) -> StdResult<()> {
+    Singleton::new(storage, KEY_CIRCUIT).save(data)?;
+    Ok(())
+}
+
+pub fn read_circuit<S: Storage>(storage: &S) -> StdResult<Circuit> {
+    ReadonlySingleton::new(storage, KEY_CIRCUIT).load()
+}
+
+pub fn store_circuit_limits<S: Storage>(storage: &mut S, denom: &str, data: &CircuitLimits) -> StdResult<()> {
+    let key = "limits".to_string() + denom;
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_circuit_limits<S: Storage>(storage: &S, denom: &str) -> StdResult<CircuitLimits> {
+    let key = "limits".to_string() + denom;
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_loss_window<S: Storage>(storage: &mut S, denom: &str, data: &LossWindow) -> StdResult<()> {
+    let key = "losswindow".to_string() + denom;
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_loss_window<S: Storage>(storage: &S, denom: &str) -> StdResult<LossWindow> {
+    let key = "losswindow".to_string() + denom;
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_escrow<S: Storage>(storage: &mut S, game: &HumanAddr, round: u64, data: &RoundEscrow) -> StdResult<()> {
+    let key = "escrow".to_string() + game.as_str() + "/" + round.to_string().as_str();
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_escrow<S: Storage>(storage: &S, game: &HumanAddr, round: u64) -> StdResult<RoundEscrow> {
+    let key = "escrow".to_string() + game.as_str() + "/" + round.to_string().as_str();
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn remove_escrow<S: Storage>(storage: &mut S, game: &HumanAddr, round: u64) {
+    let key = "escrow".to_string() + game.as_str() + "/" + round.to_string().as_str();
+    storage.remove(key.as_bytes());
+}
+
+pub fn store_round_record<S: Storage>(storage: &mut S, game: &HumanAddr, data: &RoundRecord) -> StdResult<()> {
+    let key = "settled".to_string() + game.as_str() + "/" + data.round_id.to_string().as_str();
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_round_record<S: Storage>(storage: &S, game: &HumanAddr, round: u64) -> StdResult<Option<RoundRecord>> {
+    let key = "settled".to_string() + game.as_str() + "/" + round.to_string().as_str();
+    may_load(storage, key.as_bytes())
+}
+
+pub fn store_referral_config<S: Storage>(storage: &mut S, data: &ReferralConfig) -> StdResult<()> {
+    Singleton::new(storage, KEY_REFERRAL_CONFIG).save(data)?;
+    Ok(())
+}
+
+pub fn read_referral_config<S: Storage>(storage: &S) -> StdResult<ReferralConfig> {
+    ReadonlySingleton::new(storage, KEY_REFERRAL_CONFIG).load()
+}
+
+pub fn store_referrer<S: Storage>(storage: &mut S, player: &HumanAddr, referrer: &HumanAddr) -> StdResult<()> {
+    let key = "referrer".to_string() + player.as_str();
+    save(storage, key.as_bytes(), referrer)
+}
+
+pub fn read_referrer<S: Storage>(storage: &S, player: &HumanAddr) -> StdResult<Option<HumanAddr>> {
+    let key = "referrer".to_string() + player.as_str();
+    may_load(storage, key.as_bytes())
+}
+
+pub fn store_referral_rewards<S: Storage>(storage: &mut S, referrer: &HumanAddr, denom: &str, data: &ReferralRewards) -> StdResult<()> {
+    let key = "referral".to_string() + referrer.as_str() + "/" + denom;
+    save(storage, key.as_bytes(), data)
+}
+
+pub fn read_referral_rewards<S: Storage>(storage: &S, referrer: &HumanAddr, denom: &str) -> StdResult<ReferralRewards> {
+    let key = "referral".to_string() + referrer.as_str() + "/" + denom;
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+/// Stats are kept for a denomination or a game address, in total or for one day
+fn stats_key(scope: &str, day: Option<u64>) -> String {
+    match day {
+        Some(day) => "stats".to_string() + scope + "/" + day.to_string().as_str(),
+        None => "stats".to_string() + scope,
+    }
+}
+
+pub fn store_stats<S: Storage>(storage: &mut S, scope: &str, day: Option<u64>, data: &Stats) -> StdResult<()> {
+    save(storage, stats_key(scope, day).as_bytes(), data)
+}
+
+pub fn read_stats<S: Storage>(storage: &S, scope: &str, day: Option<u64>) -> StdResult<Option<Stats>> {
+    may_load(storage, stats_key(scope, day).as_bytes())
+}
+
+/// Appends an entry to the ledger, its id is set to its position
+pub fn append_ledger_entry<S: Storage>(storage: &mut S, entry: &mut LedgerEntry) -> StdResult<()> {
+    let len : u64 = may_load(storage, KEY_LEDGER_LEN)?.unwrap_or(0);
+
+    entry.id = len;
+    let key = "ledger".to_string() + len.to_string().as_str();
+    save(storage, key.as_bytes(), entry)?;
+    save(storage, KEY_LEDGER_LEN, &(len + 1))?;
+
+    Ok(())
+}
+
+/// Returns up to `limit` entries after the `start_after` id, oldest first, and the total number of entries
+pub fn read_ledger<S: Storage>(storage: &S, start_after: Option<u64>, limit: u32) -> StdResult<(Vec<LedgerEntry>, u64)> {
+    let len : u64 = may_load(storage, KEY_LEDGER_LEN)?.unwrap_or(0);
+
+    let start = start_after.map_or(0, |id| id.saturating_add(1));
+    let mut entries = vec![];
+    for id in start..start.saturating_add(u64::from(limit)).min(len) {
+        let key = "ledger".to_string() + id.to_string().as_str();
+        entries.push(load(storage, key.as_bytes())?);
+    }
+
+    Ok((entries, len))
+}
+
 pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
     storage.set(key, &Bincode2::serialize(value)?);
     Ok(())
@@ -67,3 +363,9 @@ pub fn load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) ->
     )
 }
 
+pub fn may_load<T: DeserializeOwned, S: ReadonlyStorage>(storage: &S, key: &[u8]) -> StdResult<Option<T>> {
+    match storage.get(key) {
+        Some(value) => Bincode2::deserialize(&value).map(Some),
+        None => Ok(None),
+    }
+}
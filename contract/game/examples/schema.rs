@@ -3,8 +3,7 @@ use std::fs::create_dir_all;
 
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
-use secret_contract_example::msg::{CountResponse, HandleMsg, InitMsg, QueryMsg};
-use secret_contract_example::state::State;
+use secret_jack_game::msg::{HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, TableView};
 
 fn main() {
     let mut out_dir = current_dir().unwrap();
@@ -14,7 +13,8 @@ fn main() {
 
     export_schema(&schema_for!(InitMsg), &out_dir);
     export_schema(&schema_for!(HandleMsg), &out_dir);
+    export_schema(&schema_for!(HandleAnswer), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
-    export_schema(&schema_for!(State), &out_dir);
-    export_schema(&schema_for!(CountResponse), &out_dir);
+    export_schema(&schema_for!(QueryAnswer), &out_dir);
+    export_schema(&schema_for!(TableView), &out_dir);
 }
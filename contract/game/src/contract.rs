@@ -1,15 +1,208 @@
-use cosmwasm_std::{to_binary, Api, Binary, Env, Extern, HandleResponse, HandleResult, InitResponse, Querier, StdError, StdResult, Storage, Uint128, HumanAddr, CosmosMsg, BankMsg, Coin, WasmMsg, WasmQuery, QueryRequest, debug_print};
+use cosmwasm_std::{to_binary, from_binary, Api, Binary, Env, Extern, HandleResponse, HandleResult, InitResponse, Querier, StdError, StdResult, Storage, Uint128, HumanAddr, CosmosMsg, Coin, WasmMsg, WasmQuery, QueryRequest, debug_print};
 use rand::prelude::SliceRandom;
 use rand::SeedableRng;
-use rs_poker::core::{Card, Deck, Value};
+use rs_poker::core::{Card, Deck, Suit, Value};
 use crate:: {
-    msg::{PlayerHand, HandleMsg, InitMsg, QueryAnswer, QueryMsg, Table, Player, PlayerState, GameDeck, GameState, Scores, PlayerResult},
-    state:: {read_raw_scores, store_scores, read_secret, zero_user_balance, store_secret, store_table, read_table, read_raw_table, read_user_balance, read_bank_address, read_bank_code_hash, add_user_balance, store_bank_address, store_bank_code_hash, store_game_address, read_deck, store_deck, read_player_secret, store_player_secret},
+    msg::{PlayerHand, HandleAnswer, HandleMsg, InitMsg, QueryAnswer, QueryMsg, QueryWithPermit, ReceiveMsg, ResponseStatus, ContractStatus, TableConfig, Token, JackpotConfig, JackpotHand, CardSuit, BetPolicy, Table, Player, PlayerState, GameDeck, GameState, Scores, PlayerResult, CardView, HandRecord, HandView, SeatView, TableView, TurnView, MAX_BLOCK_SIZE, MAX_HISTORY_PAGE},
+    state:: {read_raw_scores, read_scores, store_scores, read_secret, zero_user_balance, store_secret, store_table, read_table, read_user_balance, read_bank_address, read_bank_code_hash, add_user_balance, store_bank_address, store_bank_code_hash, store_game_address, read_game_address, read_deck, store_deck, read_player_secret, store_player_secret,
+              store_round, read_round, append_hand_record, read_hand_history, read_player_stats, store_player_stats, store_referrer, read_referrer, store_config, read_config, store_status, read_status, PREFIX_REVOKED_PERMITS},
 };
 use rand_chacha::ChaChaRng;
+use secret_toolkit::permit::{validate, Permit, RevokedPermits, TokenPermissions};
+use secret_toolkit::utils::{pad_handle_result, pad_query_result};
+use secret_toolkit::viewing_key::{ViewingKey, ViewingKeyStore};
+use serde_json_wasm as serde_json;
 use sha2::{Digest, Sha256};
 
 mod bank_msg;
+mod snip20_msg;
+
+/// Idle time, in seconds, after which a player can be kicked from their seat
+const KICK_TIMEOUT: u64 = 90;
+
+/// The most the bank may have to pay for a stake, a blackjack pays 1.25 times the bet.
+/// This table offers no doubles, splits or side bets, so nothing else adds to the exposure.
+pub fn max_payout(stake: Uint128) -> Uint128 {
+    Uint128::from((stake.u128() * 125) / 100)
+}
+
+fn bank_message<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: &bank_msg::HandleMsg,
+) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: read_bank_address(&deps.storage)?,
+        callback_code_hash: read_bank_code_hash(&deps.storage)?,
+        msg: to_binary(msg)?,
+        send: vec![],
+    }))
+}
+
+fn bank_query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: &bank_msg::QueryMsg,
+) -> StdResult<bank_msg::QueryAnswer> {
+    deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        callback_code_hash: read_bank_code_hash(&deps.storage)?,
+        contract_addr: read_bank_address(&deps.storage)?,
+        msg: to_binary(msg)?,
+    }))
+}
+
+fn token_message(token: &Token, msg: &snip20_msg::HandleMsg) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: token.address.clone(),
+        callback_code_hash: token.code_hash.clone(),
+        msg: to_binary(msg)?,
+        send: vec![],
+    }))
+}
+
+/// Denomination the table takes bets in, as the bank knows it. A token is known by its address
+fn stake_denom(config: &TableConfig) -> String {
+    match &config.token {
+        Some(token) => token.address.to_string(),
+        None => config.denom.clone(),
+    }
+}
+
+/// Sends funds to the bank in the table's native coin, along with `msg`, or in its token, along with `receive_msg`
+fn bank_payment_message<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    amount: Uint128,
+    msg: &bank_msg::HandleMsg,
+    receive_msg: &bank_msg::ReceiveMsg,
+) -> StdResult<CosmosMsg> {
+    let config = read_config(&deps.storage)?;
+    match config.token {
+        Some(token) => token_message(&token, &snip20_msg::HandleMsg::Send {
+            recipient: read_bank_address(&deps.storage)?,
+            amount,
+            msg: Some(to_binary(receive_msg)?),
+            padding: None,
+        }),
+        None => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: read_bank_address(&deps.storage)?,
+            callback_code_hash: read_bank_code_hash(&deps.storage)?,
+            msg: to_binary(msg)?,
+            send: vec![Coin::new(amount.u128(), &config.denom)],
+        })),
+    }
+}
+
+/// Amount a bid pays on top of its stake
+fn jackpot_contribution(config: &TableConfig, jackpot: bool) -> StdResult<Uint128> {
+    match (&config.jackpot, jackpot) {
+        (_, false) => Ok(Uint128::from(0_u128)),
+        (Some(jackpot), true) => Ok(jackpot.contribution),
+        (None, true) => Err(StdError::generic_err("This table has no jackpot")),
+    }
+}
+
+/// Padding to a zero block size would panic, and very large blocks only waste gas
+fn check_block_size(block_size: u16) -> StdResult<()> {
+    if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+        return Err(StdError::generic_err(format!("Block size should be between 1 and {}", MAX_BLOCK_SIZE)));
+    }
+
+    Ok(())
+}
+
+fn check_jackpot_config(jackpot: &JackpotConfig) -> StdResult<()> {
+    if jackpot.contribution.u128() == 0 {
+        return Err(StdError::generic_err("Jackpot contribution should be set"));
+    }
+
+    if jackpot.paytable.iter().any(|line| line.pool_bps == 0 || line.pool_bps > 10000) {
+        return Err(StdError::generic_err("A jackpot hand should win between 1 and 10000 basis points of the pool"));
+    }
+
+    Ok(())
+}
+
+fn check_bet_config(config: &TableConfig) -> StdResult<()> {
+    if config.bet_step.u128() == 0 || config.min_bet.u128() == 0 || config.min_bet.u128() % config.bet_step.u128() != 0 {
+        return Err(StdError::generic_err("Minimum bet should be a multiple of the bet step, and neither can be zero"));
+    }
+
+    if config.max_bet.map_or(false, |max_bet| max_bet < config.min_bet) {
+        return Err(StdError::generic_err("Maximum bet can't be below the minimum bet"));
+    }
+
+    let valid = match config.bet_policy {
+        BetPolicy::Fixed => true,
+        BetPolicy::FreeBankroll { bps } => bps > 0 && bps <= 10000,
+        BetPolicy::Kelly { house_edge_bps } => house_edge_bps > 0 && house_edge_bps <= 10000,
+    };
+    if !valid {
+        return Err(StdError::generic_err(format!("Invalid bet policy {:?}", config.bet_policy)));
+    }
+
+    Ok(())
+}
+
+/// Smallest and largest bid the table takes, the bankroll policies depend on the bank's free funds.
+/// The largest bid is rounded down to the bet step, and is below the smallest one when the bank can't take bets
+fn bet_limits<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &TableConfig,
+) -> StdResult<(Uint128, Uint128)> {
+    let policy_max = match config.bet_policy {
+        BetPolicy::Fixed => u128::MAX,
+        BetPolicy::FreeBankroll { bps } => bankroll_max_bet(deps, config, bps)?,
+        BetPolicy::Kelly { house_edge_bps } => bankroll_max_bet(deps, config, house_edge_bps)?,
+    };
+
+    let max = config.max_bet.map_or(policy_max, |max_bet| max_bet.u128().min(policy_max));
+    Ok((config.min_bet, Uint128::from(max - max % config.bet_step.u128())))
+}
+
+/// Largest bet whose maximum payout a share of the bank's free funds covers.
+/// Bets in play are already reserved by the bank and out of its free funds, so one seat is enough
+fn bankroll_max_bet<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    config: &TableConfig,
+    share_bps: u16,
+) -> StdResult<u128> {
+    let free = match bank_query(deps, &bank_msg::QueryMsg::GetBankBalance { denom: stake_denom(config) })? {
+        bank_msg::QueryAnswer::GetBankBalance { free, .. } => free.u128(),
+        _ => return Err(StdError::generic_err("Unexpected answer from the bank")),
+    };
+
+    Ok(free * u128::from(share_bps) / 10000 * 100 / 125)
+}
+
+fn card_suit(suit: CardSuit) -> Suit {
+    match suit {
+        CardSuit::Spade => Suit::Spade,
+        CardSuit::Club => Suit::Club,
+        CardSuit::Heart => Suit::Heart,
+        CardSuit::Diamond => Suit::Diamond,
+    }
+}
+
+fn is_jackpot_hand(jackpot_hand: &JackpotHand, hand: &PlayerHand) -> bool {
+    match jackpot_hand {
+        JackpotHand::TripleSeven => {
+            hand.cards.len() >= 3
+                && hand.cards[..3].iter().all(|card| card.value == Value::Seven)
+        }
+        JackpotHand::SuitedNatural { suit } => {
+            hand.cards.len() == 2
+                && get_player_score(hand) == 21
+                && hand.cards[0].suit == hand.cards[1].suit
+                && suit.map_or(true, |suit| card_suit(suit) == hand.cards[0].suit)
+        }
+    }
+}
+
+/// Largest share of the jackpot pool the hand wins, in basis points
+pub fn jackpot_share(jackpot: &JackpotConfig, hand: &PlayerHand) -> Option<u16> {
+    jackpot.paytable.iter()
+        .filter(|line| is_jackpot_hand(&line.hand, hand))
+        .map(|line| line.pool_bps)
+        .max()
+}
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
@@ -20,12 +213,24 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     store_game_address(&mut deps.storage, &env.contract.address)?;
     store_bank_code_hash(&mut deps.storage, &msg.bank_code_hash)?;
     store_secret(&mut deps.storage, &msg.secret)?;
+    store_round(&mut deps.storage, &0)?;
+    store_status(&mut deps.storage, &ContractStatus::Normal)?;
+    let config = msg.config.unwrap_or_default();
+    check_block_size(config.block_size)?;
+    if let Some(jackpot) = &config.jackpot {
+        check_jackpot_config(jackpot)?;
+    }
+    check_bet_config(&config)?;
+    store_config(&mut deps.storage, &config)?;
+
+    let mut viewing_key_seed : Vec<u8> = msg.secret.to_be_bytes().to_vec();
+    viewing_key_seed.extend(env.contract.address.as_str().as_bytes());
+    ViewingKey::set_seed(&mut deps.storage, &Sha256::digest(&viewing_key_seed));
 
+    let empty_seat = Player{ address: Default::default(), alias: String::new(), hand: None, state: PlayerState::NotPlaying, jackpot: false };
     let table = Table {
         players_count: 0,
-        players: [Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying }, Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying },
-                  Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying }, Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying },
-                  Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying }, Player{ address: Default::default(), hand: None, state: PlayerState::NotPlaying }, ],
+        players: [empty_seat.clone(), empty_seat.clone(), empty_seat.clone(), empty_seat.clone(), empty_seat.clone(), empty_seat],
         dealer_hand: None,
         state: GameState::NoPlayers
     };
@@ -36,6 +241,7 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         players: [None, None, None, None,None,None],
         dealer: PlayerResult {
             address: HumanAddr::default(),
+            alias: String::new(),
             won: false,
             score: 0,
             reward: Uint128::from(0_u128)
@@ -57,10 +263,17 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
         callback_code_hash: msg.bank_code_hash,
         msg: to_binary(&bank_msg::HandleMsg::UpdateGameAddress {
             address: env.contract.address.clone(),
+            padding: None,
         })?,
         send: vec![],
     })]);
 
+    if let Some(token) = &config.token {
+        messages.push(token_message(token, &snip20_msg::HandleMsg::RegisterReceive {
+            code_hash: env.contract_code_hash.clone(),
+            padding: None,
+        })?);
+    }
 
     Ok(InitResponse {
         messages,
@@ -161,12 +374,54 @@ pub fn get_player_seat(table: &Table, address: &HumanAddr) -> StdResult<u8> {
     Err(StdError::not_found("Player"))
 }
 
+/// Builds the name a player is shown by on the public table, a random looking alias unless they picked a nickname
+fn get_seat_alias<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    table: &Table,
+    address: &HumanAddr,
+    seat: u8,
+    secret: u64,
+    nickname: Option<String>,
+) -> StdResult<String> {
+    let alias = match nickname {
+        Some(nickname) => {
+            if nickname.is_empty() || nickname.len() > 16 || !nickname.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                return Err(StdError::generic_err("Nickname should be 1-16 letters, digits, '_' or '-'"))
+            }
+
+            nickname
+        }
+        None => {
+            let mut alias_seed : Vec<u8> = vec![];
+            alias_seed.extend(address.as_str().as_bytes());
+            alias_seed.extend(&secret.to_be_bytes());
+            alias_seed.extend(&read_round(&deps.storage)?.to_be_bytes());
+            alias_seed.push(seat);
+
+            let digest = Sha256::digest(&alias_seed);
+            let mut alias = "Player-".to_string();
+            for byte in digest.iter().take(3) {
+                alias.push_str(format!("{:02x}", byte).as_str());
+            }
+
+            alias
+        }
+    };
+
+    if table.players.iter().any(|player| !player.address.is_empty() && player.alias == alias) {
+        return Err(StdError::generic_err("Nickname is already used at this table"))
+    }
+
+    Ok(alias)
+}
+
 pub fn add_player<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     table: &mut Table,
     address: &HumanAddr,
     seat: u8,
     secret: u64,
+    nickname: Option<String>,
 ) -> StdResult<()> {
     if seat >= 6 {
         return Err(StdError::generic_err("No such seat"))
@@ -176,12 +431,15 @@ pub fn add_player<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Player already seated"))
     }
 
+    let alias = get_seat_alias(deps, table, address, seat, secret, nickname)?;
+
     let player : &mut Player = table.players.get_mut(usize::from(seat)).unwrap();
     if !player.address.is_empty() {
         return Err(StdError::generic_err("Seat already taken"))
     }
 
     player.address = address.clone();
+    player.alias = alias;
     table.players_count += 1;
     store_player_secret(&mut deps.storage, seat, &secret)?;
 
@@ -193,7 +451,7 @@ pub fn remove_player<S: Storage, A: Api, Q: Querier>(
     table: &mut Table,
     address: &HumanAddr,
     seat: u8,
-) -> StdResult<()> {
+) -> StdResult<Uint128> {
     if seat >= 6 {
         return Err(StdError::generic_err("No such seat"));
     }
@@ -203,18 +461,21 @@ pub fn remove_player<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Wrong address for seated player"))
     }
 
+    let stake = read_user_balance(&deps.storage, address)?;
+    zero_user_balance(&mut deps.storage, address)?;
+
     player.address = Default::default();
+    player.alias = String::new();
     player.hand = None;
     player.state = PlayerState::NotPlaying;
+    player.jackpot = false;
 
     table.players_count -= 1;
 
-    zero_user_balance(&mut deps.storage, &player.address)?;
-
     let empty_player_secret: u64 = 0;
     store_player_secret(&mut deps.storage, seat, &empty_player_secret)?;
 
-    Ok(())
+    Ok(stake)
 }
 
 pub fn get_player_score(deck: &PlayerHand) -> u8 {
@@ -257,13 +518,17 @@ pub fn play_dealer<S: Storage, A: Api, Q: Querier>(
 
 pub fn game_roundup<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
-    env: &Env,
+    _env: &Env,
     table: &mut Table,
     out_msgs: &mut Vec<CosmosMsg>,
 ) -> StdResult<()> {
     let dealer_score = get_player_score(table.dealer_hand.as_ref().unwrap());
+    let round = read_round(&deps.storage)?;
+    let jackpot = read_config(&deps.storage)?.jackpot;
+    let mut exposure: u128 = 0;
+    let mut results: Vec<bank_msg::Settlement> = vec![];
     let mut scores = Scores { players: [None,None,None,None,None,None],
-        dealer: PlayerResult{ address: HumanAddr::default(), won: false, score: dealer_score, reward:Uint128::from(0_u128) } };
+        dealer: PlayerResult{ address: HumanAddr::default(), alias: String::new(), won: false, score: dealer_score, reward:Uint128::from(0_u128) } };
 
     for seat in 0..6 {
         let player = get_player(table, None, seat)?;
@@ -275,43 +540,53 @@ pub fn game_roundup<S: Storage, A: Api, Q: Querier>(
             PlayerState::Hold => {
                 let player_balance = read_user_balance(&deps.storage, &player.address)?;
                 let player_score = get_player_score(player.hand.as_ref().unwrap());
+                let jackpot_bps = match &jackpot {
+                    Some(jackpot) if player.jackpot => jackpot_share(jackpot, player.hand.as_ref().unwrap()),
+                    _ => None,
+                };
+                exposure += max_payout(player_balance).u128();
                 if (player_score <= 21) && (( player_score > dealer_score) || dealer_score > 21)  {
                     let mut player_award = player_balance.u128();
                     if player_score == 21 {
                         player_award = ((player_award * 125) / 100) as u128;
                     }
-                    out_msgs.push( CosmosMsg::Wasm(WasmMsg::Execute {
-                        contract_addr: read_bank_address(&deps.storage)?,
-                        callback_code_hash: read_bank_code_hash(&deps.storage)?,
-                        msg: to_binary(&bank_msg::HandleMsg::PayToWinner {
-                            amount: Uint128::from(player_award),
-                            to: player.address.clone(),
-                        })?,
-                        send: vec![],
-                    }));
-                    out_msgs.push(CosmosMsg::Bank(BankMsg::Send {
-                        from_address: env.contract.address.clone(),
-                        to_address: player.address.clone(),
-                        amount: vec![Coin::new(player_balance.u128(), "uscrt")],
-                    }));
+                    results.push(bank_msg::Settlement {
+                        player: player.address.clone(),
+                        payout: Uint128::from(player_balance.u128() + player_award),
+                        jackpot_bps,
+                    });
 
-                    scores.players[usize::from(seat)] = Some(PlayerResult{ address: player.address.clone(), won: true, score: player_score, reward: Uint128::from(player_award) });
+                    scores.players[usize::from(seat)] = Some(PlayerResult{ address: player.address.clone(), alias: player.alias.clone(), won: true, score: player_score, reward: Uint128::from(player_award) });
                 } else {
-                    out_msgs.push(CosmosMsg::Bank(BankMsg::Send {
-                        from_address: env.contract.address.clone(),
-                        to_address: read_bank_address(&deps.storage)?,
-                        amount: vec![Coin::new(player_balance.u128(), "uscrt")],
-                    }));
+                    results.push(bank_msg::Settlement { player: player.address.clone(), payout: Uint128::from(0_u128), jackpot_bps });
 
-                    scores.players[usize::from(seat)] = Some(PlayerResult{ address: player.address.clone(), won: false, score: player_score , reward: player_balance});
+                    scores.players[usize::from(seat)] = Some(PlayerResult{ address: player.address.clone(), alias: player.alias.clone(), won: false, score: player_score , reward: player_balance});
                 }
 
+                record_hand(deps, player, seat, round, dealer_score, scores.players[usize::from(seat)].as_ref().unwrap(), player_balance)?;
                 zero_user_balance(&mut deps.storage, &player.address)?;
             }
             _ => { continue; }
         }
 
         player.state = PlayerState::NotPlaying;
+        player.jackpot = false;
+    }
+
+    // Release the round's exposure before paying, so the bank doesn't count it as reserved while paying out
+    if exposure > 0 {
+        out_msgs.push(bank_message(deps, &bank_msg::HandleMsg::ReleaseExposure {
+            amount: Uint128::from(exposure),
+            padding: None,
+        })?);
+    }
+    // Stakes of players that were kicked during the round aren't in the results, the bank keeps them
+    if !results.is_empty() {
+        out_msgs.push(bank_message(deps, &bank_msg::HandleMsg::SettleRound {
+            round_id: round,
+            results,
+            padding: None,
+        })?);
     }
 
     store_scores(&mut deps.storage, &scores)?;
@@ -319,6 +594,41 @@ pub fn game_roundup<S: Storage, A: Api, Q: Querier>(
     Ok(())
 }
 
+fn record_hand<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    player: &Player,
+    seat: u8,
+    round: u64,
+    dealer_score: u8,
+    result: &PlayerResult,
+    stake: Uint128,
+) -> StdResult<()> {
+    let reward = if result.won { result.reward } else { Uint128::from(0_u128) };
+    let record = HandRecord {
+        round,
+        seat,
+        cards: player.hand.as_ref().unwrap().cards.iter().map(CardView::from).collect(),
+        score: result.score,
+        dealer_score,
+        won: result.won,
+        stake,
+        reward,
+    };
+    append_hand_record(&mut deps.storage, &player.address, &record)?;
+
+    let mut stats = read_player_stats(&deps.storage, &player.address)?;
+    stats.hands_played += 1;
+    if result.won {
+        stats.hands_won += 1;
+    }
+    if record.cards.len() == 2 && result.score == 21 {
+        stats.blackjacks += 1;
+    }
+    stats.total_wagered = stats.total_wagered + stake;
+    stats.total_won = stats.total_won + reward;
+    store_player_stats(&mut deps.storage, &player.address, &stats)
+}
+
 pub fn on_game_state_change<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: &Env,
@@ -483,6 +793,7 @@ pub fn start_new_round<S: Storage, A: Api, Q: Querier>(
         let player = get_player(table, None, seat)?;
         player.hand = None;
     }
+    table.dealer_hand = None;
 
     match get_first_player_to_play(table) {
         Ok(seat) => {
@@ -492,6 +803,9 @@ pub fn start_new_round<S: Storage, A: Api, Q: Querier>(
                 turn_start_time: env.block.time
             };
 
+            let round = read_round(&deps.storage)?;
+            store_round(&mut deps.storage, &(round + 1))?;
+
             let secret = read_secret(&deps.storage)?;
             let seed = get_random_seed(deps, table, secret)?;
             store_secret(&mut deps.storage, &(secret + 1))?;
@@ -507,7 +821,6 @@ pub fn start_new_round<S: Storage, A: Api, Q: Querier>(
             store_deck(&mut deps.storage, &deck)?;
         },
         Err(_) =>  {
-            table.dealer_hand = None;
             table.state = GameState::NoPlayers;
         },
     }
@@ -561,11 +874,43 @@ pub fn is_any_player_holding(table: &mut Table) -> StdResult<bool> {
     Ok(found)
 }
 
+/// Records the player's referrer and tells the bank, which accrues the referrer's rewards
+fn referral_message<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    player: &HumanAddr,
+    referrer: HumanAddr,
+) -> StdResult<CosmosMsg> {
+    if *player == referrer {
+        return Err(StdError::generic_err("A player can't refer themselves"));
+    }
+
+    store_referrer(&mut deps.storage, player, &referrer)?;
+    bank_message(deps, &bank_msg::HandleMsg::RegisterReferral { player: player.clone(), referrer, padding: None })
+}
+
+pub fn register_referrer<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    referrer: HumanAddr,
+) -> HandleResult {
+    if read_referrer(&deps.storage, &env.message.sender)?.is_some() {
+        return Err(StdError::generic_err("Referrer was already registered"));
+    }
+
+    Ok(HandleResponse {
+        messages: vec![referral_message(deps, &env.message.sender, referrer)?],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RegisterReferrer { status: ResponseStatus::Success })?),
+    })
+}
+
 pub fn sit<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     seat: u8,
     secret: u64,
+    nickname: Option<String>,
+    referrer: Option<HumanAddr>,
 ) -> HandleResult {
     let mut table = read_table(&deps.storage)?;
     let prev_state = table.state.clone();
@@ -577,7 +922,14 @@ pub fn sit<S: Storage, A: Api, Q: Querier>(
 
     let mut msgs: Vec<CosmosMsg> = vec![];
 
-    add_player(deps, &mut table,&env.message.sender, seat, secret)?;
+    // Only the first referrer counts, a returning player's referrer is left as is
+    if let Some(referrer) = referrer {
+        if read_referrer(&deps.storage, &env.message.sender)?.is_none() {
+            msgs.push(referral_message(deps, &env.message.sender, referrer)?);
+        }
+    }
+
+    add_player(deps, &mut table,&env.message.sender, seat, secret, nickname)?;
     if let GameState::NoPlayers = prev_state {
         table.state = GameState::PlayerTurn { player_seat: seat, is_first: true, turn_start_time: 0 };
         on_game_state_change(deps, &env, &mut table, &prev_state, &mut msgs)?;
@@ -654,6 +1006,67 @@ pub fn bid<S: Storage, A: Api, Q: Querier>(
     env: Env,
     seat: u8,
     amount: Uint128,
+    jackpot: bool,
+) -> HandleResult {
+    let config = read_config(&deps.storage)?;
+    if let Some(token) = &config.token {
+        return Err(StdError::generic_err(format!(
+            "This table takes bets in {}, send them through the token contract",
+            token.address,
+        )));
+    }
+
+    let total = Uint128::from(amount.u128() + jackpot_contribution(&config, jackpot)?.u128());
+    if !env.message.sent_funds.contains(&Coin{ denom: config.denom.clone(), amount: total }) {
+        return Err(StdError::generic_err(format!(
+            "Wrong amount sent. Requested amount is {} {}",
+            total,
+            config.denom,
+        )));
+    }
+
+    let player = env.message.sender.clone();
+    place_bid(deps, env, &player, seat, amount, jackpot)
+}
+
+pub fn receive<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    from: HumanAddr,
+    amount: Uint128,
+    msg: Option<Binary>,
+) -> HandleResult {
+    let config = read_config(&deps.storage)?;
+    match &config.token {
+        Some(token) if token.address == env.message.sender => {}
+        _ => return Err(StdError::generic_err("This table doesn't take bets in this token")),
+    }
+
+    let msg: ReceiveMsg = match msg {
+        Some(msg) => from_binary(&msg)?,
+        None => return Err(StdError::generic_err("Tokens were sent without an action")),
+    };
+
+    match msg {
+        ReceiveMsg::Bid { seat, jackpot } => {
+            let jackpot = jackpot.unwrap_or(false);
+            let stake = match amount.u128().checked_sub(jackpot_contribution(&config, jackpot)?.u128()) {
+                Some(stake) => stake,
+                None => return Err(StdError::generic_err("Tokens sent don't cover the jackpot contribution")),
+            };
+            place_bid(deps, env, &from, seat, Uint128::from(stake), jackpot)
+        }
+    }
+}
+
+/// Places a bid whose stake, and jackpot contribution, were already received in the table's native coin or token
+fn place_bid<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    address: &HumanAddr,
+    seat: u8,
+    amount: Uint128,
+    jackpot: bool,
 ) -> HandleResult {
     let mut table = read_table(&deps.storage)?;
 
@@ -688,35 +1101,30 @@ pub fn bid<S: Storage, A: Api, Q: Querier>(
         return Err(StdError::generic_err("Amount should be set"));
     }
 
-    if !env.message.sent_funds.contains(&Coin{ denom: "uscrt".to_string(), amount }) {
+    let config = read_config(&deps.storage)?;
+    let (min_bid, max_bid) = bet_limits(deps, &config)?;
+    if amount < min_bid || amount > max_bid {
         return Err(StdError::generic_err(format!(
-            "Wrong amount sent. Requested amount is {} uscrt",
-            amount,
+            "Bid should be between {} and {} {}",
+            min_bid,
+            max_bid,
+            stake_denom(&config),
         )));
     }
 
-    let response = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        callback_code_hash: read_bank_code_hash(&deps.storage)?,
-        contract_addr: read_bank_address(&deps.storage)?,
-        msg: to_binary(&bank_msg::QueryMsg::GetBankBalance {})?,
-    }))?;
-
-    match response {
-        bank_msg::QueryAnswer::GetBankBalance { balance } => {
-            let max_bid_allowed = ((balance.u128() * 100) / (125 * 6)) as u128;
-            if amount.u128() > max_bid_allowed {
-                return Err(StdError::generic_err(format!(
-                    "Max bid allowed is {} uscrt",
-                    max_bid_allowed,
-                )));
-            }
-        }
+    if amount.u128() % config.bet_step.u128() != 0 {
+        return Err(StdError::generic_err(format!(
+            "Bid should be a multiple of {} {}",
+            config.bet_step,
+            stake_denom(&config),
+        )));
     }
 
-    let player = get_player(&mut table, Some(&env.message.sender), seat)?;
+    let player = get_player(&mut table, Some(address), seat)?;
     player.state = PlayerState::Bid;
+    player.jackpot = jackpot;
 
-    add_user_balance(&mut deps.storage, &env.message.sender, amount)?;
+    add_user_balance(&mut deps.storage, address, amount)?;
 
     on_player_state_change(deps, player, &PlayerState::NotPlaying, &PlayerState::Bid)?;
     table.state = GameState::PlayerTurn {player_seat: seat, is_first: false, turn_start_time: env.block.time};
@@ -724,7 +1132,33 @@ pub fn bid<S: Storage, A: Api, Q: Querier>(
 
     store_table(&mut deps.storage, &table)?;
 
-    Ok(HandleResponse::default())
+    // The bank rejects the reservation, and with it the whole bid, when it can't cover the payout
+    let reserve_msg = bank_message(deps, &bank_msg::HandleMsg::ReserveExposure {
+        amount: max_payout(amount),
+        padding: None,
+    })?;
+    let round_id = read_round(&deps.storage)?;
+    let mut messages = vec![bank_payment_message(
+        deps,
+        amount,
+        &bank_msg::HandleMsg::EscrowStake { round_id, player: address.clone(), padding: None },
+        &bank_msg::ReceiveMsg::EscrowStake { round_id, player: address.clone() },
+    )?];
+    if jackpot {
+        messages.push(bank_payment_message(
+            deps,
+            jackpot_contribution(&config, jackpot)?,
+            &bank_msg::HandleMsg::ContributeJackpot { round_id, player: address.clone(), padding: None },
+            &bank_msg::ReceiveMsg::ContributeJackpot { round_id, player: address.clone() },
+        )?);
+    }
+    messages.push(reserve_msg);
+
+    Ok(HandleResponse {
+        messages,
+        log: vec![],
+        data: None
+    })
 }
 
 pub fn hit<S: Storage, A: Api, Q: Querier>(
@@ -765,7 +1199,6 @@ pub fn hit<S: Storage, A: Api, Q: Querier>(
 pub fn kick<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    target: HumanAddr,
     seat: u8,
 ) -> HandleResult {
     let mut table = read_table(&deps.storage)?;
@@ -778,7 +1211,7 @@ pub fn kick<S: Storage, A: Api, Q: Querier>(
                 return Err(StdError::generic_err("Player can kick only playing player"))
             }
 
-            if (env.block.time - turn_start_time) < 90 { // 1.5 minutes
+            if (env.block.time - turn_start_time) < KICK_TIMEOUT {
                 return Err(StdError::generic_err("Player can be kicked only after 1.5 minutes of idle time"))
             }
 
@@ -786,7 +1219,20 @@ pub fn kick<S: Storage, A: Api, Q: Querier>(
         _ => return Err(StdError::generic_err("Player can bid only on his turn"))
     }
 
-    remove_player(deps, &mut table, &target, seat)?;
+    // Addresses are hidden on private tables, so the idle player is found by the seat alone
+    let target = table.players[usize::from(seat)].address.clone();
+    if target.is_empty() {
+        return Err(StdError::generic_err("Seat isn't occupied"));
+    }
+
+    let stake = remove_player(deps, &mut table, &target, seat)?;
+    if stake.u128() > 0 {
+        msgs.push(bank_message(deps, &bank_msg::HandleMsg::ReleaseExposure {
+            amount: max_payout(stake),
+            padding: None,
+        })?);
+    }
+
     advance_to_next_player(deps, &env, &mut table, seat, true)?;
 
     on_game_state_change(deps, &env, &mut table, &prev_state, &mut msgs)?;
@@ -799,20 +1245,114 @@ pub fn kick<S: Storage, A: Api, Q: Querier>(
     })
 }
 
+pub fn create_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entropy: String,
+) -> HandleResult {
+    let key = ViewingKey::create(&mut deps.storage, &env, &env.message.sender, entropy.as_bytes());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::CreateViewingKey { key })?),
+    })
+}
+
+pub fn set_viewing_key<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    key: String,
+) -> HandleResult {
+    ViewingKey::set(&mut deps.storage, &env.message.sender, key.as_str());
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetViewingKey { status: ResponseStatus::Success })?),
+    })
+}
+
+pub fn revoke_permit<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    permit_name: String,
+) -> HandleResult {
+    RevokedPermits::revoke_permit(&mut deps.storage, PREFIX_REVOKED_PERMITS, &env.message.sender, &permit_name);
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::RevokePermit { status: ResponseStatus::Success })?),
+    })
+}
+
+pub fn set_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    status: ContractStatus,
+) -> HandleResult {
+    match bank_query(deps, &bank_msg::QueryMsg::GetRoles {})? {
+        bank_msg::QueryAnswer::GetRoles { operator, .. } if operator == env.message.sender => {}
+        _ => return Err(StdError::unauthorized()),
+    }
+
+    store_status(&mut deps.storage, &status)?;
+
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![],
+        data: Some(to_binary(&HandleAnswer::SetStatus { status: ResponseStatus::Success })?),
+    })
+}
+
+/// Rejects the messages the contract status doesn't allow
+fn check_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: &HandleMsg,
+) -> StdResult<()> {
+    match read_status(&deps.storage)? {
+        ContractStatus::Normal => Ok(()),
+        ContractStatus::StopBets => match msg {
+            HandleMsg::Sit { .. } | HandleMsg::Bid { .. } | HandleMsg::Receive { .. } => {
+                Err(StdError::generic_err("Table isn't taking new bets"))
+            }
+            _ => Ok(()),
+        },
+        ContractStatus::StopAll => match msg {
+            HandleMsg::SetStatus { .. }
+            | HandleMsg::CreateViewingKey { .. }
+            | HandleMsg::SetViewingKey { .. }
+            | HandleMsg::RevokePermit { .. } => Ok(()),
+            _ => Err(StdError::generic_err("Table is stopped")),
+        },
+    }
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    match msg {
-        HandleMsg::Hold {seat} => hold(deps, env, seat),
-        HandleMsg::HoldIfBust {seat} => hold_if_bust(deps, env, seat),
-        HandleMsg::Bid {seat, amount} => bid(deps, env, seat, amount),
-        HandleMsg::Sit {seat, secret} => sit(deps, env, seat, secret),
-        HandleMsg::Stand {seat} => stand(deps, env, seat),
-        HandleMsg::Kick { target, seat } => kick(deps, env, target, seat),
-        HandleMsg::Hit { seat } => hit(deps, env, seat),
-    }
+    check_status(deps, &msg)?;
+
+    let response = match msg {
+        HandleMsg::Hold { seat, .. } => hold(deps, env, seat),
+        HandleMsg::HoldIfBust { seat, .. } => hold_if_bust(deps, env, seat),
+        HandleMsg::Bid { seat, amount, jackpot, .. } => bid(deps, env, seat, amount, jackpot.unwrap_or(false)),
+        HandleMsg::Sit { seat, secret, nickname, referrer, .. } => sit(deps, env, seat, secret, nickname, referrer),
+        HandleMsg::RegisterReferrer { referrer, .. } => register_referrer(deps, env, referrer),
+        HandleMsg::Stand { seat, .. } => stand(deps, env, seat),
+        HandleMsg::Kick { seat, .. } => kick(deps, env, seat),
+        HandleMsg::Hit { seat, .. } => hit(deps, env, seat),
+        HandleMsg::CreateViewingKey { entropy, .. } => create_viewing_key(deps, env, entropy),
+        HandleMsg::SetViewingKey { key, .. } => set_viewing_key(deps, env, key),
+        HandleMsg::RevokePermit { permit_name, .. } => revoke_permit(deps, env, permit_name),
+        HandleMsg::Receive { from, amount, msg, .. } => receive(deps, env, from, amount, msg),
+        HandleMsg::SetStatus { status, .. } => set_status(deps, env, status),
+    };
+
+    pad_handle_result(response, usize::from(read_config(&deps.storage)?.block_size))
 }
 
 fn get_user_balance<S: Storage, A: Api, Q: Querier>(
@@ -823,41 +1363,252 @@ fn get_user_balance<S: Storage, A: Api, Q: Querier>(
     to_binary(&QueryAnswer::GetUserBalance { balance })
 }
 
+fn get_hand_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+    page: Option<u32>,
+    page_size: u32,
+) -> StdResult<Binary> {
+    let (hands, total) = read_hand_history(&deps.storage, address, page.unwrap_or(0), page_size.min(MAX_HISTORY_PAGE))?;
+    to_binary(&QueryAnswer::GetHandHistory { hands, total })
+}
+
+fn get_stats<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let stats = read_player_stats(&deps.storage, address)?;
+    to_binary(&QueryAnswer::GetStats { stats })
+}
+
+fn get_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetStatus { status: read_status(&deps.storage)? })
+}
+
+fn get_bet_limits<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    seat: u8,
+) -> StdResult<Binary> {
+    if seat >= 6 {
+        return Err(StdError::generic_err("No such seat"));
+    }
+
+    let config = read_config(&deps.storage)?;
+    let (min, max) = bet_limits(deps, &config)?;
+    let awaiting_bid = match read_table(&deps.storage)?.state {
+        GameState::PlayerTurn { player_seat, is_first, .. } => player_seat == seat && is_first,
+        _ => false,
+    };
+
+    to_binary(&QueryAnswer::GetBetLimits { min, max, step: config.bet_step, denom: stake_denom(&config), awaiting_bid })
+}
+
+fn get_config<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    to_binary(&QueryAnswer::GetConfig { config: read_config(&deps.storage)? })
+}
+
+fn get_jackpot<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<Binary> {
+    let config = read_config(&deps.storage)?;
+    let pool = match bank_query(deps, &bank_msg::QueryMsg::GetJackpot { denom: stake_denom(&config) })? {
+        bank_msg::QueryAnswer::GetJackpot { pool } => pool,
+        _ => return Err(StdError::generic_err("Unexpected answer from the bank")),
+    };
+
+    to_binary(&QueryAnswer::GetJackpot { config: config.jackpot, pool })
+}
+
+pub fn get_table_view(table: &Table, privacy_mode: bool) -> TableView {
+    let seats = table.players.iter().map(|player| SeatView {
+        occupied: !player.address.is_empty(),
+        player: if player.address.is_empty() || privacy_mode { None } else { Some(player.address.clone()) },
+        alias: if player.address.is_empty() { None } else { Some(player.alias.clone()) },
+        state: player.state.clone(),
+        hand: player.hand.as_ref().map(|hand| HandView {
+            cards: hand.cards.iter().map(CardView::from).collect(),
+            total_value: hand.total_value,
+        }),
+    }).collect();
+
+    let turn = match table.state {
+        GameState::NoPlayers => TurnView::NoPlayers,
+        GameState::PlayerTurn { player_seat, is_first, turn_start_time } => TurnView::PlayerTurn {
+            seat: player_seat,
+            awaiting_bid: is_first,
+            deadline: turn_start_time + KICK_TIMEOUT,
+        },
+        GameState::DealerTurn => TurnView::DealerTurn,
+    };
+
+    TableView {
+        seats,
+        dealer_up_card: table.dealer_hand.as_ref().and_then(|hand| hand.cards.first()).map(CardView::from),
+        turn,
+    }
+}
+
 fn get_table_data<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
 ) -> StdResult<Binary> {
-    let table = read_raw_table(&deps.storage)?;
-    to_binary(&QueryAnswer::GetTable { table })
+    let table = read_table(&deps.storage)?;
+    let privacy_mode = read_config(&deps.storage)?.privacy_mode;
+    to_binary(&QueryAnswer::GetTable { table: get_table_view(&table, privacy_mode) })
 }
 
 fn get_last_score<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
 ) -> StdResult<Binary> {
-    let scores = read_raw_scores(&deps.storage)?;
+    let mut scores = read_raw_scores(&deps.storage)?;
+    if read_config(&deps.storage)?.privacy_mode {
+        let mut private_scores = read_scores(&deps.storage)?;
+        for result in private_scores.players.iter_mut().flatten() {
+            result.address = HumanAddr::default();
+        }
+
+        scores = serde_json::to_vec(&private_scores).map_err(|e| StdError::serialize_err("Scores", e))?;
+    }
+
     to_binary(&QueryAnswer::GetLastScore { last_score: scores })
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
+fn get_my_seat<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: &HumanAddr,
+) -> StdResult<Binary> {
+    let table = read_table(&deps.storage)?;
+    let answer = match get_player_seat(&table, address) {
+        Ok(seat) => QueryAnswer::GetMySeat { seat: Some(seat), alias: Some(table.players[usize::from(seat)].alias.clone()) },
+        Err(_) => QueryAnswer::GetMySeat { seat: None, alias: None },
+    };
+
+    to_binary(&answer)
+}
+
+fn viewing_keys_queries<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     msg: QueryMsg,
 ) -> StdResult<Binary> {
+    let (address, key) = msg.get_validation_params();
+    if ViewingKey::check(&deps.storage, address, key).is_err() {
+        return to_binary(&QueryAnswer::ViewingKeyError {
+            msg: "Wrong viewing key for this address or viewing key not set".to_string(),
+        });
+    }
+
     match msg {
-        QueryMsg::GetUserBalance {address} => get_user_balance(deps, &address),
+        QueryMsg::GetUserBalance { address, .. } => get_user_balance(deps, &address),
+        QueryMsg::GetHandHistory { address, page, page_size, .. } => get_hand_history(deps, &address, page, page_size),
+        QueryMsg::GetStats { address, .. } => get_stats(deps, &address),
+        QueryMsg::GetMySeat { address, .. } => get_my_seat(deps, &address),
+        _ => panic!("This query type does not require authentication"),
+    }
+}
+
+fn permit_queries<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    permit: Permit,
+    query: QueryWithPermit,
+) -> StdResult<Binary> {
+    let game_address = read_game_address(&deps.storage)?;
+    let account = validate(deps, PREFIX_REVOKED_PERMITS, &permit, game_address)?;
+
+    match query {
+        QueryWithPermit::Balance {} => {
+            if !permit.check_permission(&TokenPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query balance, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            get_user_balance(deps, &account)
+        }
+        QueryWithPermit::HandHistory { page, page_size } => {
+            if !permit.check_permission(&TokenPermissions::History) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query hand history, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            get_hand_history(deps, &account, page, page_size)
+        }
+        QueryWithPermit::Stats {} => {
+            if !permit.check_permission(&TokenPermissions::History) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query stats, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            get_stats(deps, &account)
+        }
+        QueryWithPermit::MySeat {} => {
+            if !permit.check_permission(&TokenPermissions::Balance) {
+                return Err(StdError::generic_err(format!(
+                    "No permission to query seat, got permissions {:?}",
+                    permit.params.permissions
+                )));
+            }
+
+            get_my_seat(deps, &account)
+        }
+    }
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    let response = match msg {
         QueryMsg::GetTable { } => get_table_data(deps),
         QueryMsg::GetLastScore { } => get_last_score(deps),
-    }
+        QueryMsg::GetStatus { } => get_status(deps),
+        QueryMsg::GetJackpot { } => get_jackpot(deps),
+        QueryMsg::GetBetLimits { seat } => get_bet_limits(deps, seat),
+        QueryMsg::GetConfig { } => get_config(deps),
+        QueryMsg::WithPermit { permit, query } => permit_queries(deps, permit, query),
+        _ => viewing_keys_queries(deps, msg),
+    };
+
+    pad_query_result(response, usize::from(read_config(&deps.storage)?.block_size))
 }
 
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
-    use cosmwasm_std::{Coin, from_binary};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockQuerier};
+    use cosmwasm_std::{Coin, Empty, QuerierResult, from_binary, from_slice};
     use serde::Serialize;
     use crate::msg::{HandleMsg, InitMsg, QueryMsg, HandleAnswer, QueryAnswer};
     use crate::msg::GameState::NoPlayers;
 
+    /// Answers the bank's roles query like a bank run by "operator"
+    struct BankQuerier {
+        base: MockQuerier,
+    }
+
+    impl Querier for BankQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            if let Ok(QueryRequest::Wasm(WasmQuery::Smart { msg, .. })) = from_slice::<QueryRequest<Empty>>(bin_request) {
+                if let Ok(bank_msg::QueryMsg::GetRoles {}) = from_binary(&msg) {
+                    return Ok(to_binary(&bank_msg::QueryAnswer::GetRoles {
+                        owner: HumanAddr("owner".to_string()),
+                        operator: HumanAddr("operator".to_string()),
+                        proposed_owner: None,
+                    }));
+                }
+            }
+            self.base.raw_query(bin_request)
+        }
+    }
+
     fn validate_game_state(table: &Table, expected_state: GameState) -> bool {
         match table.state {
             GameState::NoPlayers =>
@@ -892,7 +1643,8 @@ mod tests {
         let msg = InitMsg{
             bank_address: Default::default(),
             bank_code_hash: "".to_string(),
-            secret: 1234
+            secret: 1234,
+            config: None,
         };
         let env = mock_env("sit", &[]);
 
@@ -900,7 +1652,7 @@ mod tests {
 
         // No previous players
         assert!(validate_game_state(&read_table(&deps.storage).unwrap(), GameState::NoPlayers));
-        let msg = HandleMsg::Sit { secret: 4321, seat: 4 };
+        let msg = HandleMsg::Sit { secret: 4321, seat: 4, nickname: None, referrer: None, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
@@ -914,7 +1666,7 @@ mod tests {
             turn_start_time: 0
         }));
 
-        let msg = HandleMsg::Sit { secret: 4321, seat: 5 };
+        let msg = HandleMsg::Sit { secret: 4321, seat: 5, nickname: None, referrer: None, padding: None };
 
         let nenv = mock_env("new_sit", &[]);
         let res = handle(&mut deps, nenv.clone(), msg.clone());
@@ -937,7 +1689,8 @@ mod tests {
         let msg = InitMsg{
             bank_address: Default::default(),
             bank_code_hash: "".to_string(),
-            secret: 1234
+            secret: 1234,
+            config: None,
         };
         let env = mock_env("unsit", &[]);
 
@@ -945,7 +1698,7 @@ mod tests {
 
 
         // No other players
-        let msg = HandleMsg::Sit { secret: 4321, seat: 4 };
+        let msg = HandleMsg::Sit { secret: 4321, seat: 4, nickname: None, referrer: None, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
@@ -956,30 +1709,30 @@ mod tests {
             turn_start_time: 0
         }));
 
-        let msg = HandleMsg::Stand { seat: 5 };
+        let msg = HandleMsg::Stand { seat: 5, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_err(), "Seat wasn't occupied");
 
-        let msg = HandleMsg::Stand { seat: 4 };
+        let msg = HandleMsg::Stand { seat: 4, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
 
         assert!(validate_game_state(&read_table(&deps.storage).unwrap(), GameState::NoPlayers));
 
-        let msg = HandleMsg::Sit { secret: 4321, seat: 4 };
+        let msg = HandleMsg::Sit { secret: 4321, seat: 4, nickname: None, referrer: None, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
 
         let nenv = mock_env("new_unsit", &[]);
-        let msg = HandleMsg::Sit { secret: 4321, seat: 5 };
+        let msg = HandleMsg::Sit { secret: 4321, seat: 5, nickname: None, referrer: None, padding: None };
 
         let res = handle(&mut deps, nenv.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
 
-        let msg = HandleMsg::Stand { seat: 4 };
+        let msg = HandleMsg::Stand { seat: 4, padding: None };
 
         let res = handle(&mut deps, env.clone(), msg.clone());
         assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
@@ -990,6 +1743,429 @@ mod tests {
             turn_start_time: 0
         }));
     }
-}
 
+    #[test]
+    fn test_escrow_settlement() {
+        let mut deps = mock_dependencies(20,  &[]);
+
+        let msg = InitMsg{
+            bank_address: HumanAddr("bank".to_string()),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: Some(TableConfig { bet_policy: BetPolicy::Fixed, ..TableConfig::default() }),
+        };
+        init(&mut deps, mock_env("bank", &[]), msg).unwrap();
+
+        let player = HumanAddr("player".to_string());
+        let msg = HandleMsg::Sit { secret: 4321, seat: 0, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+
+        // The stake goes straight into the bank's escrow for the round
+        let msg = HandleMsg::Bid { amount: Uint128(100), seat: 0, jackpot: None, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(100, "uscrt")]), msg).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, send, .. }) => {
+                assert_eq!(contract_addr, &HumanAddr("bank".to_string()));
+                assert_eq!(msg, &to_binary(&bank_msg::HandleMsg::EscrowStake { round_id: 1, player: player.clone(), padding: None }).unwrap());
+                assert_eq!(send, &vec![Coin::new(100, "uscrt")]);
+            }
+            _ => panic!("Expected the stake to be escrowed in the bank"),
+        }
+
+        // Holding ends the round, which is settled by the bank in a single message
+        let res = handle(&mut deps, mock_env("player", &[]), HandleMsg::Hold { seat: 0, padding: None }).unwrap();
+        let (history, total) = read_hand_history(&deps.storage, &player, 0, 10).unwrap();
+        assert_eq!(total, 1);
+        let record = &history[0];
+        assert_eq!(record.round, 1);
+        assert_eq!(record.stake, Uint128(100));
+
+        let settlements: Vec<bank_msg::HandleMsg> = res.messages.iter().filter_map(|message| match message {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, send, .. }) => {
+                assert!(send.is_empty(), "The game holds no funds to pay from");
+                match from_binary::<bank_msg::HandleMsg>(msg).unwrap() {
+                    settle @ bank_msg::HandleMsg::SettleRound { .. } => Some(settle),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }).collect();
+        assert_eq!(settlements.len(), 1);
+        match &settlements[0] {
+            bank_msg::HandleMsg::SettleRound { round_id, results, .. } => {
+                assert_eq!(*round_id, 1);
+                assert_eq!(results.len(), 1);
+                assert_eq!(results[0].player, player);
+                let payout = if record.won { record.stake + record.reward } else { Uint128(0) };
+                assert_eq!(results[0].payout, payout);
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(read_user_balance(&deps.storage, &player).unwrap(), Uint128(0));
+    }
+
+    #[test]
+    fn test_table_view() {
+        let mut deps = mock_dependencies(20,  &[]);
+
+        let msg = InitMsg{
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: None,
+        };
+        init(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 2, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+
+        let mut table = read_table(&deps.storage).unwrap();
+        table.dealer_hand = Some(PlayerHand {
+            cards: vec![Card { value: Value::Ace, suit: Suit::Spade }, Card { value: Value::King, suit: Suit::Heart }],
+            total_value: 21,
+        });
+        store_table(&mut deps.storage, &table).unwrap();
+        store_deck(&mut deps.storage, &GameDeck {
+            deck: vec![Card { value: Value::Queen, suit: Suit::Diamond }],
+            next_free_card: 0,
+        }).unwrap();
+
+        let res = query(&deps, QueryMsg::GetTable { }).unwrap();
+        let raw = String::from_utf8(res.as_slice().to_vec()).unwrap();
+        assert!(!raw.contains("Queen") && !raw.contains("next_free_card"), "The deck should stay hidden");
+        assert!(!raw.contains("King"), "The dealer's hole card should stay hidden");
+
+        let table = match from_binary(&res).unwrap() {
+            QueryAnswer::GetTable { table } => table,
+            _ => panic!("Unexpected answer"),
+        };
+        assert_eq!(table.dealer_up_card, Some(CardView { value: "Ace".to_string(), suit: "Spade".to_string() }));
+        assert_eq!(table.seats.len(), 6);
+        assert_eq!(table.seats[2].player, Some(HumanAddr::from("player")));
+        assert!(!table.seats[3].occupied);
+        assert_eq!(table.turn, TurnView::PlayerTurn { seat: 2, awaiting_bid: true, deadline: mock_env("player", &[]).block.time + KICK_TIMEOUT });
+    }
+
+    #[test]
+    fn test_private_table_kick() {
+        let mut deps = mock_dependencies(20,  &[]);
+
+        let msg = InitMsg{
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: Some(TableConfig { privacy_mode: true, ..TableConfig::default() }),
+        };
+        init(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 1, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("idle", &[]), msg).unwrap();
+        let msg = HandleMsg::Sit { secret: 4321, seat: 3, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("other", &[]), msg).unwrap();
+
+        let table = match from_binary(&query(&deps, QueryMsg::GetTable { }).unwrap()).unwrap() {
+            QueryAnswer::GetTable { table } => table,
+            _ => panic!("Unexpected answer"),
+        };
+        for seat in [1, 3].iter() {
+            let seat = &table.seats[*seat];
+            assert!(seat.occupied);
+            assert_eq!(seat.player, None, "Addresses are hidden on a private table");
+            assert!(seat.alias.as_ref().map_or(false, |alias| !alias.is_empty() && alias != "idle" && alias != "other"));
+        }
+
+        let msg = HandleMsg::Kick { seat: 1, padding: None };
+        let res = handle(&mut deps, mock_env("other", &[]), msg.clone());
+        assert!(res.is_err(), "Player can't be kicked before the timeout");
+
+        let mut env = mock_env("other", &[]);
+        env.block.time += KICK_TIMEOUT;
+        let res = handle(&mut deps, env.clone(), HandleMsg::Kick { seat: 3, padding: None });
+        assert!(res.is_err(), "Only the player on turn can be kicked");
+
+        handle(&mut deps, env, msg).unwrap();
+
+        let table = read_table(&deps.storage).unwrap();
+        assert!(table.players[1].address.is_empty());
+        assert!(validate_game_state(&table, GameState::PlayerTurn { player_seat: 3, is_first: true, turn_start_time: 0 }));
+    }
+
+    #[test]
+    fn test_viewing_key() {
+        let mut deps = mock_dependencies(20,  &[]);
+
+        let msg = InitMsg{
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: None,
+        };
+        let env = mock_env("player", &[]);
+
+        let _init_res = init(&mut deps, env.clone(), msg).unwrap();
+
+        let msg = HandleMsg::CreateViewingKey { entropy: "entropy".to_string(), padding: None };
+        let res = handle(&mut deps, env.clone(), msg).unwrap();
+        let key = match from_binary(&res.data.unwrap()).unwrap() {
+            HandleAnswer::CreateViewingKey { key } => key,
+            _ => panic!("Unexpected handle answer"),
+        };
 
+        let msg = QueryMsg::GetUserBalance { address: HumanAddr("player".to_string()), key };
+        let res: QueryAnswer = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetUserBalance { balance: Uint128(0) });
+
+        let msg = QueryMsg::GetUserBalance { address: HumanAddr("player".to_string()), key: "wrong".to_string() };
+        let res: QueryAnswer = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        match res {
+            QueryAnswer::ViewingKeyError { .. } => {},
+            _ => panic!("Balance should not be returned for a wrong viewing key"),
+        }
+
+        let msg = HandleMsg::SetViewingKey { key: "my key".to_string(), padding: None };
+        handle(&mut deps, env.clone(), msg).unwrap();
+
+        let msg = QueryMsg::GetUserBalance { address: HumanAddr("player".to_string()), key: "my key".to_string() };
+        let res: QueryAnswer = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetUserBalance { balance: Uint128(0) });
+    }
+
+    #[test]
+    fn test_permit() {
+        let mut deps = mock_dependencies(20,  &[]);
+
+        let msg = InitMsg{
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: None,
+        };
+        init(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        // Permits signed offline for chain "secret-4" with the permit name "test"
+        let pub_key = "A+f+hho/Z9T2AEUQlf4e4zBOAbcatHGIa4Nci9zSpwTG";
+        let with_permit = |token: &str, permission: &str, signature: &str, query: &str| -> QueryMsg {
+            let msg = format!(
+                r#"{{"with_permit":{{"permit":{{"params":{{"allowed_tokens":["{}"],"chain_id":"secret-4","permissions":["{}"],"permit_name":"test"}},"signature":{{"pub_key":{{"type":"tendermint/PubKeySecp256k1","value":"{}"}},"signature":"{}"}}}},"query":{{"{}":{{}}}}}}}}"#,
+                token, permission, pub_key, signature, query,
+            );
+            cosmwasm_std::from_slice(msg.as_bytes()).unwrap()
+        };
+
+        let msg = with_permit("cosmos2contract", "balance", "jHFTed7ivqb1R+OdGZF3XQb829PGhqY+PS9p24AC2GkJttvSWFKS+/XioRxioZPCiyipy+dHD+b4snf+FFgt+A==", "balance");
+        let res: QueryAnswer = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetUserBalance { balance: Uint128(0) });
+
+        let msg = with_permit("secret1other", "balance", "2sVfzjZuc6PElzy5Huw5SAmTzsHYlbBU3/ZYs/hhBFxjk2DDMlTqhPMTO2DIVBIuzYHG6SHtwKpcga1zNxXXsQ==", "balance");
+        assert!(query(&deps, msg).is_err(), "A permit for another contract should be rejected");
+
+        let msg = with_permit("cosmos2contract", "balance", "wBhB5GRQnnKUQDMiV33V0ybEFCGanzxHN6cypXj1WYcVoaFFuWqj6xFTJKelm2iDnK7Q87kL0eT+GkGNfUea8w==", "balance");
+        assert!(query(&deps, msg).is_err(), "A permit signed by another key should be rejected");
+
+        let msg = with_permit("cosmos2contract", "history", "ueYvXIaN8D2rAbfcwjCtGByg50awadcT8ClYIi4pu65E4I+1PojLmR1rdFzU9mC8zVoVBDy2jcILe8v0eNPLcA==", "balance");
+        assert!(query(&deps, msg).is_err(), "The balance needs the balance permission");
+
+        let msg = with_permit("cosmos2contract", "balance", "jHFTed7ivqb1R+OdGZF3XQb829PGhqY+PS9p24AC2GkJttvSWFKS+/XioRxioZPCiyipy+dHD+b4snf+FFgt+A==", "my_seat");
+        let res: QueryAnswer = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetMySeat { seat: None, alias: None }, "The seat needs no more than the balance permission");
+    }
+
+    #[test]
+    fn test_status() {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let msg = InitMsg{
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: None,
+        };
+        let env = mock_env("player", &[]);
+        let _init_res = init(&mut deps, env.clone(), msg).unwrap();
+        let mut deps = Extern { storage: deps.storage, api: deps.api, querier: BankQuerier { base: deps.querier } };
+
+        let msg = HandleMsg::SetStatus { status: ContractStatus::StopAll, padding: None };
+        let res = handle(&mut deps, env.clone(), msg.clone());
+        assert!(matches!(res, Err(StdError::Unauthorized { .. })), "Only the bank's operator can change the status");
+        handle(&mut deps, mock_env("operator", &[]), msg).unwrap();
+        let res: QueryAnswer = from_binary(&query(&deps, QueryMsg::GetStatus {}).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetStatus { status: ContractStatus::StopAll });
+        store_status(&mut deps.storage, &ContractStatus::Normal).unwrap();
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 4, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, env.clone(), msg).unwrap();
+
+        let msg = HandleMsg::Stand { seat: 4, padding: None };
+        store_status(&mut deps.storage, &ContractStatus::StopAll).unwrap();
+        let res = handle(&mut deps, env.clone(), msg.clone());
+        assert!(res.is_err(), "Nothing can be played while the table is stopped");
+
+        store_status(&mut deps.storage, &ContractStatus::StopBets).unwrap();
+        let res: QueryAnswer = from_binary(&query(&deps, QueryMsg::GetStatus {}).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetStatus { status: ContractStatus::StopBets });
+
+        let sit = HandleMsg::Sit { secret: 4321, seat: 5, nickname: None, referrer: None, padding: None };
+        let res = handle(&mut deps, mock_env("new_player", &[]), sit);
+        assert!(res.is_err(), "No one can sit while bets are stopped");
+
+        let res = handle(&mut deps, env.clone(), msg);
+        assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
+    }
+
+    #[test]
+    fn test_jackpot() {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let mut config = TableConfig {
+            jackpot: Some(JackpotConfig {
+                contribution: Uint128(10),
+                paytable: vec![
+                    crate::msg::JackpotPay { hand: JackpotHand::TripleSeven, pool_bps: 1000 },
+                    crate::msg::JackpotPay { hand: JackpotHand::SuitedNatural { suit: None }, pool_bps: 500 },
+                    crate::msg::JackpotPay { hand: JackpotHand::SuitedNatural { suit: Some(CardSuit::Heart) }, pool_bps: 10001 },
+                ],
+            }),
+            ..TableConfig::default()
+        };
+        let msg = InitMsg {
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: Some(config.clone()),
+        };
+        let res = init(&mut deps, mock_env("owner", &[]), msg.clone());
+        assert!(res.is_err(), "A jackpot hand can't win more than the whole pool");
+
+        config.jackpot.as_mut().unwrap().paytable[2].pool_bps = 10000;
+        let msg = InitMsg { config: Some(config.clone()), ..msg };
+        init(&mut deps, mock_env("owner", &[]), msg).unwrap();
+
+        let jackpot = config.jackpot.unwrap();
+        let hand = |cards: Vec<Card>| PlayerHand { total_value: cards.iter().map(get_card_value).sum(), cards };
+        let card = |value: Value, suit: Suit| Card { value, suit };
+
+        let sevens = hand(vec![card(Value::Seven, Suit::Spade), card(Value::Seven, Suit::Club), card(Value::Seven, Suit::Heart)]);
+        assert_eq!(jackpot_share(&jackpot, &sevens), Some(1000));
+
+        let sevens = hand(vec![card(Value::Seven, Suit::Spade), card(Value::Two, Suit::Club), card(Value::Seven, Suit::Heart), card(Value::Seven, Suit::Club)]);
+        assert_eq!(jackpot_share(&jackpot, &sevens), None, "Only the first three cards count");
+
+        let natural = hand(vec![card(Value::Ace, Suit::Spade), card(Value::King, Suit::Spade)]);
+        assert_eq!(jackpot_share(&jackpot, &natural), Some(500));
+
+        let natural = hand(vec![card(Value::Ace, Suit::Heart), card(Value::Ten, Suit::Heart)]);
+        assert_eq!(jackpot_share(&jackpot, &natural), Some(10000), "The largest matching share is won");
+
+        let natural = hand(vec![card(Value::Ace, Suit::Heart), card(Value::Ten, Suit::Club)]);
+        assert_eq!(jackpot_share(&jackpot, &natural), None);
+
+        let twenty_one = hand(vec![card(Value::Seven, Suit::Heart), card(Value::Four, Suit::Heart), card(Value::King, Suit::Heart)]);
+        assert_eq!(jackpot_share(&jackpot, &twenty_one), None);
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 0, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+        let msg = HandleMsg::Bid { amount: Uint128(100), seat: 0, jackpot: Some(true), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(100, "uscrt")]), msg);
+        assert!(res.is_err(), "The jackpot contribution should be sent along with the bid");
+    }
+
+    #[test]
+    fn test_bet_limits() {
+        let mut deps = mock_dependencies(20, &[]);
+
+        let config = TableConfig { min_bet: Uint128(200), max_bet: Some(Uint128(100)), bet_policy: BetPolicy::Fixed, ..TableConfig::default() };
+        let msg = InitMsg {
+            bank_address: Default::default(),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: Some(config),
+        };
+        let res = init(&mut deps, mock_env("owner", &[]), msg.clone());
+        assert!(res.is_err(), "Minimum bet can't be above the maximum");
+
+        let config = TableConfig { min_bet: Uint128(100), bet_step: Uint128(30), bet_policy: BetPolicy::Fixed, ..TableConfig::default() };
+        let res = init(&mut deps, mock_env("owner", &[]), InitMsg { config: Some(config), ..msg.clone() });
+        assert!(res.is_err(), "Minimum bet should be a multiple of the bet step");
+
+        let config = TableConfig { block_size: 0, ..TableConfig::default() };
+        let res = init(&mut deps, mock_env("owner", &[]), InitMsg { config: Some(config), ..msg.clone() });
+        assert!(res.is_err(), "Responses can't be padded to a zero block size");
+
+        let config = TableConfig {
+            min_bet: Uint128(100),
+            max_bet: Some(Uint128(1020)),
+            bet_step: Uint128(50),
+            bet_policy: BetPolicy::Fixed,
+            ..TableConfig::default()
+        };
+        init(&mut deps, mock_env("owner", &[]), InitMsg { config: Some(config.clone()), ..msg }).unwrap();
+
+        let res: QueryAnswer = from_binary(&query(&deps, QueryMsg::GetConfig {}).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetConfig { config });
+
+        let res: QueryAnswer = from_binary(&query(&deps, QueryMsg::GetBetLimits { seat: 2 }).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetBetLimits { min: Uint128(100), max: Uint128(1000), step: Uint128(50), denom: "uscrt".to_string(), awaiting_bid: false });
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 2, nickname: None, referrer: None, padding: None };
+        handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+        let res: QueryAnswer = from_binary(&query(&deps, QueryMsg::GetBetLimits { seat: 2 }).unwrap()).unwrap();
+        assert_eq!(res, QueryAnswer::GetBetLimits { min: Uint128(100), max: Uint128(1000), step: Uint128(50), denom: "uscrt".to_string(), awaiting_bid: true });
+        assert!(query(&deps, QueryMsg::GetBetLimits { seat: 6 }).is_err());
+
+        let msg = HandleMsg::Bid { amount: Uint128(50), seat: 2, jackpot: None, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(50, "uscrt")]), msg);
+        assert!(res.is_err(), "Bid below the table minimum");
+
+        let msg = HandleMsg::Bid { amount: Uint128(1010), seat: 2, jackpot: None, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(1010, "uscrt")]), msg);
+        assert!(res.is_err(), "Bid above the table maximum");
+
+        let msg = HandleMsg::Bid { amount: Uint128(975), seat: 2, jackpot: None, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(975, "uscrt")]), msg);
+        assert!(res.is_err(), "Bid should be a multiple of the bet step");
+
+        let msg = HandleMsg::Bid { amount: Uint128(1000), seat: 2, jackpot: None, padding: None };
+        let res = handle(&mut deps, mock_env("player", &[Coin::new(1000, "uscrt")]), msg);
+        assert!(res.is_ok(), format!("{:?}", res.unwrap_err()));
+    }
+
+    #[test]
+    fn test_referrer() {
+        let mut deps = mock_dependencies(20, &[Coin::new(10000000, "uscrt")]);
+
+        let msg = InitMsg {
+            bank_address: HumanAddr("bank".to_string()),
+            bank_code_hash: "".to_string(),
+            secret: 1234,
+            config: None,
+        };
+        let _init_res = init(&mut deps, mock_env("bank", &[]), msg).unwrap();
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 0, nickname: None, referrer: Some(HumanAddr("player".to_string())), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg);
+        assert!(res.is_err(), "A player can't refer themselves");
+
+        let msg = HandleMsg::Sit { secret: 4321, seat: 0, nickname: None, referrer: Some(HumanAddr("referrer".to_string())), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg).unwrap();
+        assert_eq!(res.messages.len(), 1, "The bank is told about the referral");
+        assert_eq!(read_referrer(&deps.storage, &HumanAddr("player".to_string())).unwrap(), Some(HumanAddr("referrer".to_string())));
+
+        let msg = HandleMsg::RegisterReferrer { referrer: HumanAddr("other".to_string()), padding: None };
+        let res = handle(&mut deps, mock_env("player", &[]), msg.clone());
+        assert!(res.is_err(), "Referrer was already registered");
+
+        let res = handle(&mut deps, mock_env("other_player", &[]), msg).unwrap();
+        match &res.messages[0] {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                assert_eq!(contract_addr, &HumanAddr("bank".to_string()));
+                let expected = bank_msg::HandleMsg::RegisterReferral {
+                    player: HumanAddr("other_player".to_string()),
+                    referrer: HumanAddr("other".to_string()),
+                    padding: None,
+                };
+                assert_eq!(msg, &to_binary(&expected).unwrap());
+            }
+            _ => panic!("Expected a message to the bank"),
+        }
+    }
+}
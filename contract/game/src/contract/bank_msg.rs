@@ -7,6 +7,16 @@ pub struct InitMsg {
     pub game_contract_code_id: u64,
     pub game_contract_code_hash: String,
     pub secret : u64,
+    pub block_size: Option<u16>,
+}
+
+/// What a player gets back when a round is settled, the stake included
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Settlement {
+    pub player: HumanAddr,
+    pub payout: Uint128,
+    /// Share of the jackpot pool the player won, in basis points
+    pub jackpot_bps: Option<u16>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -14,28 +24,72 @@ pub struct InitMsg {
 pub enum HandleMsg {
     UpdateGameAddress {
         address: HumanAddr,
+        padding: Option<String>,
+    },
+    EscrowStake {
+        round_id: u64,
+        player: HumanAddr,
+        padding: Option<String>,
+    },
+    ContributeJackpot {
+        round_id: u64,
+        player: HumanAddr,
+        padding: Option<String>,
     },
-    PayToWinner {
+    RegisterReferral {
+        player: HumanAddr,
+        referrer: HumanAddr,
+        padding: Option<String>,
+    },
+    SettleRound {
+        round_id: u64,
+        results: Vec<Settlement>,
+        padding: Option<String>,
+    },
+    ReserveExposure {
         amount: Uint128,
-        to: HumanAddr,
+        padding: Option<String>,
     },
-    EmergencyWithdrawAll {}, // Dooms day command, will withdraw to an hard-coded address.
-    ChangeOwner {
-        new_owner: HumanAddr,
+    ReleaseExposure {
+        amount: Uint128,
+        padding: Option<String>,
+    },
+    EmergencyWithdrawAll { // Dooms day command, will withdraw to an hard-coded address.
+        padding: Option<String>,
     },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    GetBankBalance {},
+    GetBankBalance {
+        denom: String,
+    },
+    GetRoles {},
+    GetJackpot {
+        denom: String,
+    },
+}
+
+/// Sent as the `msg` of a SNIP-20 `Send` to the bank
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    EscrowStake {
+        round_id: u64,
+        player: HumanAddr,
+    },
+    ContributeJackpot {
+        round_id: u64,
+        player: HumanAddr,
+    },
 }
 
 /// Responses from handle function
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
-    ChangeOwner {},
+    ProposeOwner {},
 }
 
 // We define a custom struct for each query response
@@ -43,6 +97,16 @@ pub enum HandleAnswer {
 #[serde(rename_all = "snake_case")]
 pub enum QueryAnswer {
     GetBankBalance {
-        balance: Uint128,
+        free: Uint128,
+        reserved: Uint128,
+        total: Uint128,
+    },
+    GetRoles {
+        owner: HumanAddr,
+        operator: HumanAddr,
+        proposed_owner: Option<HumanAddr>,
+    },
+    GetJackpot {
+        pool: Uint128,
     },
 }
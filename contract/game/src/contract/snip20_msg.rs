@@ -0,0 +1,19 @@
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The SNIP-20 messages a token table sends to its token contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HandleMsg {
+    Send {
+        recipient: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    RegisterReceive {
+        code_hash: String,
+        padding: Option<String>,
+    },
+}
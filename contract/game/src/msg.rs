@@ -1,13 +1,117 @@
-use cosmwasm_std::{HumanAddr, Uint128};
+use cosmwasm_std::{Binary, HumanAddr, Uint128};
 use rs_poker::core::Card;
 use schemars::JsonSchema;
+use secret_toolkit::permit::Permit;
 use serde::{Deserialize, Serialize};
 
+pub const DEFAULT_BLOCK_SIZE: u16 = 256;
+pub const MAX_BLOCK_SIZE: u16 = 4096;
+pub const DEFAULT_DENOM: &str = "uscrt";
+pub const MAX_HISTORY_PAGE: u32 = 100;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InitMsg {
     pub bank_address: HumanAddr,
     pub bank_code_hash: String,
     pub secret: u64,
+    pub config: Option<TableConfig>,
+}
+
+/// Per-table settings, every field falls back to its default when omitted
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(default)]
+pub struct TableConfig {
+    /// Handle and query responses are padded to a multiple of this many bytes
+    pub block_size: u16,
+    /// Hide player addresses from the public table and scores, showing seat aliases instead
+    pub privacy_mode: bool,
+    /// Native coin the table takes bets in
+    pub denom: String,
+    /// SNIP-20 token the table takes bets in instead of `denom`
+    pub token: Option<Token>,
+    /// Opt-in side bet funding the bank's progressive jackpot, the table has none when it isn't set
+    pub jackpot: Option<JackpotConfig>,
+    /// Smallest bid, a multiple of `bet_step`
+    pub min_bet: Uint128,
+    /// Largest bid whatever the bankroll, the bet policy may lower it
+    pub max_bet: Option<Uint128>,
+    /// Bids are multiples of this amount
+    pub bet_step: Uint128,
+    pub bet_policy: BetPolicy,
+}
+
+impl Default for TableConfig {
+    fn default() -> Self {
+        TableConfig {
+            block_size: DEFAULT_BLOCK_SIZE,
+            privacy_mode: false,
+            denom: DEFAULT_DENOM.to_string(),
+            token: None,
+            jackpot: None,
+            min_bet: Uint128(1),
+            max_bet: None,
+            bet_step: Uint128(1),
+            bet_policy: BetPolicy::FreeBankroll { bps: 10000 },
+        }
+    }
+}
+
+/// How the largest bet a table takes follows the bankroll, within the table's `min_bet` and `max_bet`.
+/// Bets the bank can't cover are refused whatever the policy
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BetPolicy {
+    /// Only the table's range applies
+    Fixed,
+    /// A bet can be owed at most this share of the bank's free funds, in basis points
+    FreeBankroll {
+        bps: u16,
+    },
+    /// A bet can be owed at most the share of the bank's free funds a Kelly bettor with the house's edge would stake
+    Kelly {
+        house_edge_bps: u16,
+    },
+}
+
+/// Jackpot side bet of a table. The contributions of every table betting in the same denomination go to one pool
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotConfig {
+    /// Fixed amount a player adds to their bid to play for the jackpot
+    pub contribution: Uint128,
+    pub paytable: Vec<JackpotPay>,
+}
+
+/// Share of the jackpot pool, in basis points, a hand wins. A hand matching several lines wins the largest share
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct JackpotPay {
+    pub hand: JackpotHand,
+    pub pool_bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JackpotHand {
+    /// First three cards are sevens of any suit
+    TripleSeven,
+    /// Two card 21 in one suit, the given one when it is set
+    SuitedNatural {
+        suit: Option<CardSuit>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardSuit {
+    Spade,
+    Club,
+    Heart,
+    Diamond,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Token {
+    pub address: HumanAddr,
+    pub code_hash: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -23,7 +127,7 @@ pub struct PlayerHand {
     pub total_value: u8,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum PlayerState {
     NotPlaying,
     Bid,
@@ -34,8 +138,12 @@ pub enum PlayerState {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Player {
     pub address: HumanAddr,
+    /// Name shown on the public table instead of the address when the table runs in privacy mode
+    pub alias: String,
     pub hand: Option<PlayerHand>,
     pub state: PlayerState,
+    /// Player paid the jackpot contribution with their bid this round
+    pub jackpot: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -55,6 +163,7 @@ pub struct GameDeck {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PlayerResult {
     pub address: HumanAddr,
+    pub alias: String,
     pub won : bool,
     pub score : u8,
     pub reward: Uint128,
@@ -67,32 +176,167 @@ pub struct Scores {
     pub dealer : PlayerResult,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CardView {
+    pub value: String,
+    pub suit: String,
+}
+
+impl From<&Card> for CardView {
+    fn from(card: &Card) -> Self {
+        CardView {
+            value: format!("{:?}", card.value),
+            suit: format!("{:?}", card.suit),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HandView {
+    pub cards: Vec<CardView>,
+    pub total_value: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SeatView {
+    pub occupied: bool,
+    /// Hidden when the table runs in privacy mode
+    pub player: Option<HumanAddr>,
+    pub alias: Option<String>,
+    pub state: PlayerState,
+    pub hand: Option<HandView>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnView {
+    NoPlayers,
+    PlayerTurn {
+        seat: u8,
+        awaiting_bid: bool,
+        /// Block time after which the seated player can be kicked
+        deadline: u64,
+    },
+    DealerTurn,
+}
+
+/// Public view of the table, holding only what every player at the table may see
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TableView {
+    pub seats: Vec<SeatView>,
+    pub dealer_up_card: Option<CardView>,
+    pub turn: TurnView,
+}
+
+/// A settled hand, as kept in the player's personal history
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HandRecord {
+    pub round: u64,
+    pub seat: u8,
+    pub cards: Vec<CardView>,
+    pub score: u8,
+    pub dealer_score: u8,
+    pub won: bool,
+    pub stake: Uint128,
+    pub reward: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct PlayerStats {
+    pub hands_played: u64,
+    pub hands_won: u64,
+    pub blackjacks: u64,
+    pub total_wagered: Uint128,
+    pub total_won: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
+    /// Funds sent along are the bid's amount, and the jackpot contribution when playing for the jackpot
     Bid {
         amount: Uint128,
         seat: u8,
+        jackpot: Option<bool>,
+        padding: Option<String>,
     },
     Hold {
         seat: u8,
+        padding: Option<String>,
     },
     HoldIfBust {
         seat: u8,
+        padding: Option<String>,
     },
+    /// A player seated for the first time with a `referrer` is registered at the bank as referred by them
     Sit {
         secret: u64,
         seat: u8,
+        nickname: Option<String>,
+        referrer: Option<HumanAddr>,
+        padding: Option<String>,
+    },
+    /// Registers the sender as referred by `referrer`, a player can be referred only once
+    RegisterReferrer {
+        referrer: HumanAddr,
+        padding: Option<String>,
     },
     Stand {
         seat: u8,
+        padding: Option<String>,
     },
     Kick {
-        target: HumanAddr,
         seat: u8,
+        padding: Option<String>,
     },
     Hit {
         seat: u8,
+        padding: Option<String>,
+    },
+    CreateViewingKey {
+        entropy: String,
+        padding: Option<String>,
+    },
+    SetViewingKey {
+        key: String,
+        padding: Option<String>,
+    },
+    RevokePermit {
+        permit_name: String,
+        padding: Option<String>,
+    },
+    /// Called by the table's SNIP-20 token when a player sends it tokens, `msg` holds a `ReceiveMsg`
+    Receive {
+        sender: HumanAddr,
+        from: HumanAddr,
+        amount: Uint128,
+        msg: Option<Binary>,
+        padding: Option<String>,
+    },
+    /// Only the bank's operator can change the status
+    SetStatus {
+        status: ContractStatus,
+        padding: Option<String>,
+    },
+}
+
+/// In `StopBets` no one can sit or bid but the rounds in play can finish, `StopAll` stops the game
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractStatus {
+    Normal,
+    StopBets,
+    StopAll,
+}
+
+/// Actions paid for with SNIP-20 tokens, sent as the `msg` of the token's `Send`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Tokens sent are the bid, and the jackpot contribution when playing for the jackpot
+    Bid {
+        seat: u8,
+        jackpot: Option<bool>,
     },
 }
 
@@ -101,16 +345,87 @@ pub enum HandleMsg {
 pub enum QueryMsg {
     GetUserBalance {
         address: HumanAddr,
+        key: String,
+    },
+    GetHandHistory {
+        address: HumanAddr,
+        key: String,
+        page: Option<u32>,
+        page_size: u32,
+    },
+    GetStats {
+        address: HumanAddr,
+        key: String,
+    },
+    GetMySeat {
+        address: HumanAddr,
+        key: String,
     },
     GetTable { },
-    GetLastScore { }
+    GetLastScore { },
+    GetStatus { },
+    GetJackpot { },
+    GetConfig { },
+    GetBetLimits {
+        seat: u8,
+    },
+    WithPermit {
+        permit: Permit,
+        query: QueryWithPermit,
+    },
+}
+
+impl QueryMsg {
+    /// Address and viewing key of queries that expose per-player data
+    pub fn get_validation_params(&self) -> (&HumanAddr, &str) {
+        match self {
+            QueryMsg::GetUserBalance { address, key } => (address, key.as_str()),
+            QueryMsg::GetHandHistory { address, key, .. } => (address, key.as_str()),
+            QueryMsg::GetStats { address, key } => (address, key.as_str()),
+            QueryMsg::GetMySeat { address, key } => (address, key.as_str()),
+            _ => panic!("This query type does not require authentication"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryWithPermit {
+    Balance {},
+    HandHistory {
+        page: Option<u32>,
+        page_size: u32,
+    },
+    Stats {},
+    MySeat {},
 }
 
 /// Responses from handle function
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleAnswer {
+    CreateViewingKey {
+        key: String,
+    },
+    SetViewingKey {
+        status: ResponseStatus,
+    },
+    RevokePermit {
+        status: ResponseStatus,
+    },
+    SetStatus {
+        status: ResponseStatus,
+    },
+    RegisterReferrer {
+        status: ResponseStatus,
+    },
+}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    Success,
+    Failure,
 }
 
 // We define a custom struct for each query response
@@ -121,9 +436,42 @@ pub enum QueryAnswer {
         balance: Uint128,
     },
     GetTable {
-        table: Vec<u8>,
+        table: TableView,
     },
     GetLastScore {
         last_score: Vec<u8>,
-    }
+    },
+    GetHandHistory {
+        hands: Vec<HandRecord>,
+        total: u64,
+    },
+    GetStats {
+        stats: PlayerStats,
+    },
+    GetMySeat {
+        seat: Option<u8>,
+        alias: Option<String>,
+    },
+    GetStatus {
+        status: ContractStatus,
+    },
+    GetJackpot {
+        config: Option<JackpotConfig>,
+        /// Jackpot pool held by the bank for the table's denomination
+        pool: Uint128,
+    },
+    GetConfig {
+        config: TableConfig,
+    },
+    GetBetLimits {
+        min: Uint128,
+        max: Uint128,
+        step: Uint128,
+        denom: String,
+        /// The seat's player can bid now
+        awaiting_bid: bool,
+    },
+    ViewingKeyError {
+        msg: String,
+    },
 }
@@ -5,7 +5,9 @@ use cosmwasm_storage::{ReadonlySingleton, Singleton};
 use serde::{de::DeserializeOwned, Serialize};
 use secret_toolkit::serialization::{Bincode2, Serde};
 use serde_json_wasm as serde_json;
-use crate::msg::{Table, GameDeck, Scores};
+use crate::msg::{Table, GameDeck, Scores, HandRecord, PlayerStats, TableConfig, ContractStatus};
+
+pub const PREFIX_REVOKED_PERMITS: &str = "revoked_permits";
 
 static KEY_BANK_CODE_HASH: &[u8] = b"bankcodehash";
 static KEY_GAME_ADDRESS: &[u8] = b"gameaddress";
@@ -14,6 +16,9 @@ static KEY_TABLE: &[u8] = b"table";
 static KEY_SCORES: &[u8] = b"scores";
 static KEY_DECK: &[u8] = b"deck";
 static KEY_SECRET: &[u8] = b"secret";
+static KEY_ROUND: &[u8] = b"round";
+static KEY_CONFIG: &[u8] = b"config";
+static KEY_STATUS: &[u8] = b"status";
 
 pub fn store_secret<S: Storage>(storage: &mut S, data: &u64) -> StdResult<()> {
     Singleton::new(storage, KEY_SECRET).save(data)?;
@@ -43,6 +48,10 @@ pub fn read_raw_scores<S: Storage>(storage: &S) -> StdResult<Vec<u8>> {
     Ok(storage.get(KEY_SCORES).unwrap())
 }
 
+pub fn read_scores<S: Storage>(storage: &S) -> StdResult<Scores> {
+    Ok(serde_json::from_slice(&read_raw_scores(storage)?).unwrap())
+}
+
 pub fn read_table<S: Storage>(storage: &S) -> StdResult<Table> {
     Ok(serde_json::from_slice(&read_raw_table(storage)?).unwrap())
 }
@@ -95,6 +104,79 @@ pub fn read_user_balance<S: Storage>(storage: &S, address: &HumanAddr) -> StdRes
     })
 }
 
+pub fn store_config<S: Storage>(storage: &mut S, data: &TableConfig) -> StdResult<()> {
+    Singleton::new(storage, KEY_CONFIG).save(data)?;
+    Ok(())
+}
+
+pub fn read_config<S: Storage>(storage: &S) -> StdResult<TableConfig> {
+    ReadonlySingleton::new(storage, KEY_CONFIG).load()
+}
+
+pub fn store_status<S: Storage>(storage: &mut S, data: &ContractStatus) -> StdResult<()> {
+    Singleton::new(storage, KEY_STATUS).save(data)?;
+    Ok(())
+}
+
+pub fn read_status<S: Storage>(storage: &S) -> StdResult<ContractStatus> {
+    ReadonlySingleton::new(storage, KEY_STATUS).load()
+}
+
+pub fn store_round<S: Storage>(storage: &mut S, data: &u64) -> StdResult<()> {
+    Singleton::new(storage, KEY_ROUND).save(data)?;
+    Ok(())
+}
+
+pub fn read_round<S: Storage>(storage: &S) -> StdResult<u64> {
+    ReadonlySingleton::new(storage, KEY_ROUND).load()
+}
+
+pub fn append_hand_record<S: Storage>(storage: &mut S, address: &HumanAddr, record: &HandRecord) -> StdResult<()> {
+    let len_key = "historylen".to_string() + address.as_str();
+    let len : u64 = may_load(storage, len_key.as_bytes())?.unwrap_or(0);
+
+    let key = "history".to_string() + address.as_str() + "/" + len.to_string().as_str();
+    save(storage, key.as_bytes(), record)?;
+    save(storage, len_key.as_bytes(), &(len + 1))?;
+
+    Ok(())
+}
+
+/// Returns a page of the player's hands, newest first, and the total number of hands played
+pub fn read_hand_history<S: Storage>(storage: &S, address: &HumanAddr, page: u32, page_size: u32) -> StdResult<(Vec<HandRecord>, u64)> {
+    let len_key = "historylen".to_string() + address.as_str();
+    let len : u64 = may_load(storage, len_key.as_bytes())?.unwrap_or(0);
+
+    let skip = u64::from(page) * u64::from(page_size);
+    let mut hands = vec![];
+    for offset in skip..(skip + u64::from(page_size)).min(len) {
+        let key = "history".to_string() + address.as_str() + "/" + (len - 1 - offset).to_string().as_str();
+        hands.push(load(storage, key.as_bytes())?);
+    }
+
+    Ok((hands, len))
+}
+
+pub fn store_player_stats<S: Storage>(storage: &mut S, address: &HumanAddr, stats: &PlayerStats) -> StdResult<()> {
+    let key = "stats".to_string() + address.as_str();
+    save(storage, key.as_bytes(), stats)
+}
+
+pub fn read_player_stats<S: Storage>(storage: &S, address: &HumanAddr) -> StdResult<PlayerStats> {
+    let key = "stats".to_string() + address.as_str();
+    Ok(may_load(storage, key.as_bytes())?.unwrap_or_default())
+}
+
+pub fn store_referrer<S: Storage>(storage: &mut S, address: &HumanAddr, referrer: &HumanAddr) -> StdResult<()> {
+    let key = "referrer".to_string() + address.as_str();
+    save(storage, key.as_bytes(), referrer)
+}
+
+pub fn read_referrer<S: Storage>(storage: &S, address: &HumanAddr) -> StdResult<Option<HumanAddr>> {
+    let key = "referrer".to_string() + address.as_str();
+    may_load(storage, key.as_bytes())
+}
+
 pub fn save<T: Serialize, S: Storage>(storage: &mut S, key: &[u8], value: &T) -> StdResult<()> {
     storage.set(key, &Bincode2::serialize(value)?);
     Ok(())
@@ -148,25 +148,31 @@ async function fillUpFromFaucet(
 
 type Nullable<T> = T | null;
 type PT = {
-  PlayerTurn: {
-    player_seat: number;
-    is_first: boolean;
-    turn_start_time: number;
+  player_turn: {
+    seat: number;
+    awaiting_bid: boolean;
+    deadline: number;
   };
 };
-type GameState = string | PT;
+type Turn = string | PT;
 type Card = { value: string; suit: string };
 type Hand = { cards: Card[]; total_value: number };
-type Player = { address: string; hand: Nullable<Hand>; state: string };
+type Seat = {
+  occupied: boolean;
+  player: Nullable<string>;
+  alias: Nullable<string>;
+  state: string;
+  hand: Nullable<Hand>;
+};
 type Table = {
-  players_count: number;
-  players: Player[];
-  dealer_hand: Nullable<Hand>;
-  state: GameState;
+  seats: Seat[];
+  dealer_up_card: Nullable<Card>;
+  turn: Turn;
 };
 
 type PlayerScore = {
   address: string;
+  alias: string;
   won: boolean;
   score: number;
   reward: string;
@@ -210,17 +216,19 @@ async function initializeAndUploadContracts() {
   return clientInfo;
 }
 
+type BankBalance = { free: string; reserved: string; total: string };
+
 async function queryBankBalance(
   client: SecretNetworkClient,
   bankCodeHash: string,
   bankAddress: string
-): Promise<string> {
-  type BankBalanceResponse = { get_bank_balance: { balance: string } };
+): Promise<BankBalance> {
+  type BankBalanceResponse = { get_bank_balance: BankBalance };
 
   const bankBalanceResponse = (await client.query.compute.queryContract({
     contractAddress: bankAddress,
     codeHash: bankCodeHash,
-    query: { get_bank_balance: {} },
+    query: { get_bank_balance: { denom: "uscrt" } },
   })) as BankBalanceResponse;
 
   if ('err"' in bankBalanceResponse) {
@@ -231,7 +239,7 @@ async function queryBankBalance(
     );
   }
 
-  return bankBalanceResponse.get_bank_balance.balance;
+  return bankBalanceResponse.get_bank_balance;
 }
 
 async function withdrawAllBankFunds(
@@ -258,20 +266,21 @@ async function withdrawAllBankFunds(
   console.log(`withdraw all used ${tx.gasUsed} gas`);
 }
 
-async function payToWinner(
+const viewingKey: string = "secret-jack-integration";
+
+async function setViewingKey(
   client: SecretNetworkClient,
-  bankCodeHash: string,
-  bankAddress: string
+  gameCodeHash: string,
+  gameAddress: string
 ) {
   const tx = await client.tx.compute.executeContract(
     {
       sender: client.address,
-      contractAddress: bankAddress,
-      codeHash: bankCodeHash,
+      contractAddress: gameAddress,
+      codeHash: gameCodeHash,
       msg: {
-        pay_to_winner: {
-          amount: "1000000",
-          to: client.address,
+        set_viewing_key: {
+          key: viewingKey,
         },
       },
       sentFunds: [],
@@ -281,8 +290,7 @@ async function payToWinner(
     }
   );
 
-  //let parsedTransactionData = JSON.parse(fromUtf8(tx.data[0])); // In our case we don't really need to access transaction data
-  console.log(`pay to winner used ${tx.gasUsed} gas`);
+  console.log(`set viewing key used ${tx.gasUsed} gas`);
 }
 
 async function queryUserBalance(
@@ -296,7 +304,7 @@ async function queryUserBalance(
   const userBalanceResponse = (await client.query.compute.queryContract({
     contractAddress: gameAddress,
     codeHash: gameCodeHash,
-    query: { get_user_balance: { address: address } },
+    query: { get_user_balance: { address: address, key: viewingKey } },
   })) as UserBalanceResponse;
 
   if ('err"' in userBalanceResponse) {
@@ -315,7 +323,7 @@ async function getTable(
   gameCodeHash: string,
   gameAddress: string
 ): Promise<Table> {
-  type TableResponse = { get_table: { table: Uint8Array } };
+  type TableResponse = { get_table: { table: Table } };
 
   const tableResponse = (await client.query.compute.queryContract({
     contractAddress: gameAddress,
@@ -323,9 +331,7 @@ async function getTable(
     query: { get_table: {} },
   })) as TableResponse;
 
-  return JSON.parse(
-    new TextDecoder().decode(toArrayBuffer(tableResponse.get_table.table))
-  );
+  return tableResponse.get_table.table;
 }
 
 async function getScores(
@@ -488,8 +494,7 @@ async function kick(
   client: SecretNetworkClient,
   gameCodeHash: string,
   gameAddress: string,
-  seat: number,
-  address: string
+  seat: number
 ) {
   const tx = await client.tx.compute.executeContract(
     {
@@ -498,7 +503,6 @@ async function kick(
       codeHash: gameCodeHash,
       msg: {
         kick: {
-          target: address,
           seat: seat,
         },
       },
@@ -530,14 +534,18 @@ async function test_bank_balance_on_initialization(
   _gameCodeHash: string,
   _gameAddress: string
 ) {
-  const onInitializationBalance: string = await queryBankBalance(
+  const onInitializationBalance: BankBalance = await queryBankBalance(
     client,
     bankCodeHash,
     bankAddress
   );
   assert(
-    onInitializationBalance === "225000000",
-    `The balance on initialization expected to be 225,000,000 instead of ${onInitializationBalance}`
+    onInitializationBalance.total === "250000000",
+    `The balance on initialization expected to be 250,000,000 instead of ${onInitializationBalance.total}`
+  );
+  assert(
+    onInitializationBalance.reserved === "0" && onInitializationBalance.free === onInitializationBalance.total,
+    `Nothing should be reserved on initialization, got ${JSON.stringify(onInitializationBalance)}`
   );
 }
 
@@ -567,8 +575,8 @@ async function test_bad_bid(
   );
 }
 
-function isPlayerTurn(state: GameState): state is PT {
-  return (state as PT).PlayerTurn !== undefined;
+function isPlayerTurn(state: Turn): state is PT {
+  return (state as PT).player_turn !== undefined;
 }
 
 function isPlayerScore(score: Nullable<PlayerScore>): score is PlayerScore {
@@ -589,7 +597,7 @@ function isTheDealerWon(scores: Scores, seat: number) {
 
 async function roundup(
   client: SecretNetworkClient,
-  prevBankBalance: string,
+  prevBankBalance: BankBalance,
   players: { seat: number; prevBalance: string }[],
   bankCodeHash: string,
   bankAddress: string,
@@ -611,7 +619,7 @@ async function roundup(
       client,
       gameCodeHash,
       gameAddress,
-      table.players[player.seat].address
+      table.seats[player.seat].player!
     );
     assert(
       newUserBalance === "0",
@@ -647,14 +655,15 @@ async function roundup(
     index++;
   }
 
+  // Accrued owner fees stay in the bank until withdrawn, so the total moves by the whole awards
   let totalBankDiff = 0;
   for (let index: number = 0; index < players.length; index++) {
-    totalBankDiff -= awards[index] * 0.9;
+    totalBankDiff -= awards[index];
 
     if (awards[index] > 0) {
       const walletBalance: string = (
         await client.query.bank.balance({
-          address: table.players[players[index].seat].address,
+          address: table.seats[players[index].seat].player!,
           denom: "uscrt",
         })
       ).balance!.amount;
@@ -670,17 +679,21 @@ async function roundup(
     }
   }
 
-  const bankBalance: string = await queryBankBalance(
+  const bankBalance: BankBalance = await queryBankBalance(
     client,
     bankCodeHash,
     bankAddress
   );
 
   assert(
-    parseInt(bankBalance) === parseInt(prevBankBalance) + totalBankDiff,
+    parseInt(bankBalance.total) === parseInt(prevBankBalance.total) + totalBankDiff,
     `Expected bank balance to be equal to ${
-      parseInt(prevBankBalance) + totalBankDiff
-    } actual bank balance is ${parseInt(bankBalance)}`
+      parseInt(prevBankBalance.total) + totalBankDiff
+    } actual bank balance is ${parseInt(bankBalance.total)}`
+  );
+  assert(
+    bankBalance.reserved === "0",
+    `Exposure of the settled round should be released, ${bankBalance.reserved} is still reserved`
   );
 }
 
@@ -708,11 +721,11 @@ async function test_game_flow(
 
   let table: Table = await getTable(client, gameCodeHash, gameAddress);
   assert(
-    isPlayerTurn(table.state) && table.players[seat].state === "Bid",
+    isPlayerTurn(table.turn) && table.seats[seat].state === "Bid",
     `Expected game state is player ${seat} turn`
   );
 
-  while (table.players[seat].hand!.total_value < 17) {
+  while (table.seats[seat].hand!.total_value < 17) {
     await hit(client, gameCodeHash, gameAddress, seat);
     table = await getTable(client, gameCodeHash, gameAddress);
   }
@@ -731,7 +744,7 @@ async function test_normal_game_flow(
   await sit(client, gameCodeHash, gameAddress, seat);
 
   for (let i: number = 0; i < 3; i++) {
-    const prevBankBalance: string = await queryBankBalance(
+    const prevBankBalance: BankBalance = await queryBankBalance(
       client,
       bankCodeHash,
       bankAddress
@@ -781,7 +794,7 @@ async function test_normal_game_flow_two_players(
   await sit(client2, gameCodeHash, gameAddress, seat2);
 
   for (let i: number = 0; i < 3; i++) {
-    const prevBankBalance: string = await queryBankBalance(
+    const prevBankBalance: BankBalance = await queryBankBalance(
       client,
       bankCodeHash,
       bankAddress
@@ -802,7 +815,7 @@ async function test_normal_game_flow_two_players(
     ).balance!.amount;
 
     console.log(
-      `Iter ${i}: seat ${seat} wallet balance is: ${prevWalletBalanceP1} seat ${seat2} wallet balance is: ${prevWalletBalanceP2} bank balance is ${prevBankBalance}`
+      `Iter ${i}: seat ${seat} wallet balance is: ${prevWalletBalanceP1} seat ${seat2} wallet balance is: ${prevWalletBalanceP2} bank balance is ${prevBankBalance.total}`
     );
 
     await test_game_flow(
@@ -814,7 +827,7 @@ async function test_normal_game_flow_two_players(
       gameAddress
     );
 
-    const state = (await getTable(client, gameCodeHash, gameAddress)).state;
+    const state = (await getTable(client, gameCodeHash, gameAddress)).turn;
     assert(
       isPlayerTurn(state),
       `State expected to be player ${seat2} turn instead of ${JSON.stringify(
@@ -822,7 +835,7 @@ async function test_normal_game_flow_two_players(
       )}`
     );
     assert(
-      (state as PT).PlayerTurn.player_seat === seat2,
+      (state as PT).player_turn.seat === seat2,
       `State expected to be player ${seat2} turn instead of ${JSON.stringify(
         state
       )}`
@@ -869,7 +882,7 @@ async function test_stand_while_other_is_playing(
   await sit(client, gameCodeHash, gameAddress, seat);
   await sit(client2, gameCodeHash, gameAddress, seat2);
 
-  const prevBankBalance: string = await queryBankBalance(
+  const prevBankBalance: BankBalance = await queryBankBalance(
     client,
     bankCodeHash,
     bankAddress
@@ -898,13 +911,13 @@ async function test_stand_while_other_is_playing(
 
   let table: Table = await getTable(client, gameCodeHash, gameAddress);
   assert(
-    isPlayerTurn(table.state) && table.players[seat].state === "Bid",
+    isPlayerTurn(table.turn) && table.seats[seat].state === "Bid",
     `Expected game state is player ${seat} turn`
   );
 
   await stand(client2, gameCodeHash, gameAddress, seat2);
 
-  while (table.players[seat].hand!.total_value < 17) {
+  while (table.seats[seat].hand!.total_value < 17) {
     await hit(client, gameCodeHash, gameAddress, seat);
     table = await getTable(client, gameCodeHash, gameAddress);
   }
@@ -912,7 +925,7 @@ async function test_stand_while_other_is_playing(
   await hold(client, gameCodeHash, gameAddress, seat);
 
   table = await getTable(client, gameCodeHash, gameAddress);
-  const state = table.state;
+  const state = table.turn;
   assert(
     isPlayerTurn(state),
     `State expected to be player ${seat} turn instead of ${JSON.stringify(
@@ -920,7 +933,7 @@ async function test_stand_while_other_is_playing(
     )}`
   );
   assert(
-    (state as PT).PlayerTurn.player_seat === seat,
+    (state as PT).player_turn.seat === seat,
     `State expected to be player ${seat} turn instead of ${JSON.stringify(
       state
     )}`
@@ -955,7 +968,7 @@ async function test_game_flow_two_players_stand(
   for (let i: number = 0; i < 3; i++) {
     await sit(client2, gameCodeHash, gameAddress, seat2);
 
-    const prevBankBalance: string = await queryBankBalance(
+    const prevBankBalance: BankBalance = await queryBankBalance(
       client,
       bankCodeHash,
       bankAddress
@@ -977,7 +990,7 @@ async function test_game_flow_two_players_stand(
       gameAddress
     );
 
-    const state = (await getTable(client, gameCodeHash, gameAddress)).state;
+    const state = (await getTable(client, gameCodeHash, gameAddress)).turn;
     assert(
       isPlayerTurn(state),
       `State expected to be player ${seat2} turn instead of ${JSON.stringify(
@@ -985,7 +998,7 @@ async function test_game_flow_two_players_stand(
       )}`
     );
     assert(
-      (state as PT).PlayerTurn.player_seat === seat2,
+      (state as PT).player_turn.seat === seat2,
       `State expected to be player ${seat2} turn instead of ${JSON.stringify(
         state
       )}`
@@ -1023,7 +1036,7 @@ async function test_game_flow_two_players_kick(
   await sit(client, gameCodeHash, gameAddress, seat);
   await sit(client2, gameCodeHash, gameAddress, seat2);
 
-  const prevBankBalance: string = await queryBankBalance(
+  const prevBankBalance: BankBalance = await queryBankBalance(
     client,
     bankCodeHash,
     bankAddress
@@ -1045,7 +1058,7 @@ async function test_game_flow_two_players_kick(
     gameAddress
   );
 
-  const state = (await getTable(client, gameCodeHash, gameAddress)).state;
+  const state = (await getTable(client, gameCodeHash, gameAddress)).turn;
   assert(
     isPlayerTurn(state),
     `State expected to be player ${seat2} turn instead of ${JSON.stringify(
@@ -1053,7 +1066,7 @@ async function test_game_flow_two_players_kick(
     )}`
   );
   assert(
-    (state as PT).PlayerTurn.player_seat === seat2,
+    (state as PT).player_turn.seat === seat2,
     `State expected to be player ${seat2} turn instead of ${JSON.stringify(
       state
     )}`
@@ -1061,7 +1074,7 @@ async function test_game_flow_two_players_kick(
 
   await delay(2 * 60 * 1000);
 
-  await kick(client2, gameCodeHash, gameAddress, seat2, client2.address);
+  await kick(client2, gameCodeHash, gameAddress, seat2);
 
   await roundup(
     client,
@@ -1138,6 +1151,9 @@ async function runTwoPlayersFunction(
 
   await fillUpFromFaucet(client2, 1_000_000_000);
 
+  await setViewingKey(client, gameCodeHash, gameAddress);
+  await setViewingKey(client2, gameCodeHash, gameAddress);
+
   await runTestFunction(
     test_bank_balance_on_initialization,
     client,
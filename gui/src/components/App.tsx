@@ -15,25 +15,32 @@ declare global {
 
 type Nullable<T> = T | null;
 type PT = {
-  PlayerTurn: {
-    player_seat: number;
-    is_first: boolean;
-    turn_start_time: number;
+  player_turn: {
+    seat: number;
+    awaiting_bid: boolean;
+    deadline: number;
   };
 };
-type GameState = string | PT;
+type Turn = string | PT;
 type Card = { value: string; suit: string };
 type H = { cards: Card[]; total_value: number };
-type Player = { address: string; hand: Nullable<H>; state: string };
+type Seat = {
+  occupied: boolean;
+  player: Nullable<string>;
+  alias: Nullable<string>;
+  state: string;
+  hand: Nullable<H>;
+};
 type Table = {
-  players_count: number;
-  players: Player[];
-  dealer_hand: Nullable<H>;
-  state: GameState;
+  seats: Seat[];
+  dealer_up_card: Nullable<Card>;
+  turn: Turn;
 };
+type MySeat = { seat: number; alias: string };
 
 type PlayerScore = {
   address: string;
+  alias: string;
   won: boolean;
   score: number;
   reward: string;
@@ -43,8 +50,8 @@ type Scores = {
   dealer: PlayerScore;
 };
 
-function isPlayerTurn(state: GameState): state is PT {
-  return (state as PT).PlayerTurn !== undefined;
+function isPlayerTurn(turn: Turn): turn is PT {
+  return typeof turn !== "string" && (turn as PT).player_turn !== undefined;
 }
 
 function hasHand(hand: Nullable<H>): hand is H {
@@ -107,53 +114,17 @@ const App: React.FC = () => {
   const [address, setAddress] = useState("");
 
   const [table, setTable] = useState<Table>({
-    players_count: 0,
-    players: [{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },{
-      address: '',
-      hand: {
-        cards: [],
-        total_value: 0
-      },
-      state: ''
-    },],
-    dealer_hand: null,
-    state: ''
+    seats: Array.from({ length: 6 }, () => ({
+      occupied: false,
+      player: null,
+      alias: null,
+      state: 'NotPlaying',
+      hand: null,
+    })),
+    dealer_up_card: null,
+    turn: 'no_players'
   });
+  const [mySeat, setMySeat] = useState<MySeat>({ seat: -1, alias: '' });
 
   const [balance, setBalance] = useState(0);
   const [walletBalance, setWalletBalance] = useState(0);
@@ -219,7 +190,7 @@ const App: React.FC = () => {
     gameCodeHash: string,
     gameAddress: string
   ): Promise<Table> => {
-    type TableResponse = { get_table: { table: Uint8Array } };
+    type TableResponse = { get_table: { table: Table } };
 
     const tableResponse = (await client.query.compute.queryContract({
       contractAddress: gameAddress,
@@ -227,9 +198,7 @@ const App: React.FC = () => {
       query: { get_table: {} },
     })) as TableResponse;
 
-    return JSON.parse(
-      new TextDecoder().decode(toArrayBuffer(tableResponse.get_table.table))
-    );
+    return tableResponse.get_table.table;
   }
 
   async function getScores(
@@ -252,26 +221,108 @@ const App: React.FC = () => {
     );
   }
 
+  const viewingKeyStorageKey = (walletAddress: string) => {
+    return `viewing_key_${gameAddress}_${walletAddress}`;
+  }
+
+  // Per-player queries need a viewing key, it is set once per wallet and kept in the browser
+  const setupViewingKey = async (client: SecretNetworkClient): Promise<void> => {
+    if (localStorage.getItem(viewingKeyStorageKey(client.address)) !== null) {
+      return;
+    }
+
+    const entropy = new Uint8Array(32);
+    window.crypto.getRandomValues(entropy);
+    const key = "api_key_" + btoa(String.fromCharCode.apply(null, Array.from(entropy)));
+
+    const tx = await client.tx.compute.executeContract(
+      {
+        sender: client.address,
+        contractAddress: gameAddress,
+        codeHash: gameCodeHash,
+        msg: {
+          set_viewing_key: {
+            key: key,
+          },
+        },
+        sentFunds: [],
+      },
+      {
+        gasLimit: 100000,
+      }
+    );
+
+    if(tx.code !== 0) {
+      console.warn(tx.rawLog);
+      return;
+    }
+
+    localStorage.setItem(viewingKeyStorageKey(client.address), key);
+  }
+
   const getUserBalance = async (
     client: SecretNetworkClient,
     gameCodeHash: string,
     gameAddress: string
   ): Promise<number> => {
-    type UserBalanceResponse = { get_user_balance: { balance: string } };
+    type UserBalanceResponse = {
+      get_user_balance?: { balance: string };
+      viewing_key_error?: { msg: string };
+    };
+
+    const key = localStorage.getItem(viewingKeyStorageKey(client.address));
+    if (key === null) {
+      return 0;
+    }
 
     const userBalanceResponse = (await client.query.compute.queryContract({
       contractAddress: gameAddress,
       codeHash: gameCodeHash,
-      query: { get_user_balance: { address: address } },
+      query: { get_user_balance: { address: client.address, key: key } },
     })) as UserBalanceResponse;
 
+    if (typeof userBalanceResponse.get_user_balance === "undefined") {
+      console.warn(userBalanceResponse.viewing_key_error);
+      return 0;
+    }
+
     return parseInt(userBalanceResponse.get_user_balance.balance);
   }
 
+  // Addresses are hidden on private tables, so the player's seat comes from an authenticated query
+  const getMySeat = async (
+    client: SecretNetworkClient,
+    gameCodeHash: string,
+    gameAddress: string
+  ): Promise<MySeat> => {
+    type MySeatResponse = {
+      get_my_seat?: { seat: Nullable<number>; alias: Nullable<string> };
+      viewing_key_error?: { msg: string };
+    };
+
+    const key = localStorage.getItem(viewingKeyStorageKey(client.address));
+    if (key === null) {
+      return { seat: -1, alias: '' };
+    }
+
+    const mySeatResponse = (await client.query.compute.queryContract({
+      contractAddress: gameAddress,
+      codeHash: gameCodeHash,
+      query: { get_my_seat: { address: client.address, key: key } },
+    })) as MySeatResponse;
+
+    if (typeof mySeatResponse.get_my_seat === "undefined" || mySeatResponse.get_my_seat.seat === null) {
+      return { seat: -1, alias: '' };
+    }
+
+    return { seat: mySeatResponse.get_my_seat.seat, alias: mySeatResponse.get_my_seat.alias || '' };
+  }
+
   const refreshTableState = async () => {
     const newTable = await getTable(client!, gameCodeHash, gameAddress);
 
     setTable(newTable);
+    setMySeat(await getMySeat(client!, gameCodeHash, gameAddress));
     setBalance(await getUserBalance(client!, gameCodeHash, gameAddress));
 
     const balance = await client!.query.bank.balance({
@@ -347,6 +398,7 @@ const App: React.FC = () => {
     }
 
     if(onlyOnce && isAddressOk()) {
+      setupViewingKey(client!);
       setInterval(refreshTableState, 1000);
       setOnlyOnce(false);
     }
@@ -364,21 +416,21 @@ const App: React.FC = () => {
 
     const updatePlayerButtons = () => {
       for (let i:number = 0; i < 6; i++) {
-        if (table.players[i].address === '') {
+        if (!table.seats[i].occupied) {
           newSitButtonsState[i].disabled = (findMySeat(table) !== -1);
           newKickButtonsState[i].canBeKicked = false;
           newKickButtonsState[i].kickTimer = 0;
         } else {
           newSitButtonsState[i].disabled = true;
-          if (isPlayerTurn(table.state)) {
-            const pt = (table.state as PT).PlayerTurn;
-            if(pt.player_seat !== i) {
+          if (isPlayerTurn(table.turn)) {
+            const pt = (table.turn as PT).player_turn;
+            if(pt.seat !== i) {
               newKickButtonsState[i].canBeKicked = false;
               newKickButtonsState[i].kickTimer = 0;
             } else {
               
               newKickButtonsState[i].canBeKicked = (findMySeat(table) !== i);
-              let timer = 90 - (Math.round(Date.now() / 1000) - pt.turn_start_time);
+              let timer = pt.deadline - Math.round(Date.now() / 1000);
               if(timer < 0) {
                 timer = 0;
               }
@@ -394,20 +446,20 @@ const App: React.FC = () => {
     }
     
     const parseTableState = () => {
-      if(!isPlayerTurn(table.state)) {
-        if (table.state === "NoPlayers") {
+      if(!isPlayerTurn(table.turn)) {
+        if (table.turn === "no_players") {
           newMessage = Message.sit;
           return;
         } 
         
-        if(table.state === "DealerTurn") {
+        if(table.turn === "dealer_turn") {
           newMessage = Message.dealerTurn;
           return;
         }
 
       } 
       
-      const pt = (table.state as PT).PlayerTurn;
+      const pt = (table.turn as PT).player_turn;
       const seat = findMySeat(table);
 
       if(seat === -1) {
@@ -415,7 +467,7 @@ const App: React.FC = () => {
         return;
       }
       
-      const ps = table.players[seat].state;
+      const ps = table.seats[seat].state;
       switch(ps) {
         case 'Bid':
         case 'Hit':
@@ -428,12 +480,12 @@ const App: React.FC = () => {
       }
         
       let playerScore = 0;
-      if(hasHand(table.players[seat].hand)) {
+      if(hasHand(table.seats[seat].hand)) {
         playerScore = getPlayerScore(seat);
       }
         
-      if(pt.player_seat !== seat) {
-        if (seat > pt.player_seat) {
+      if(pt.seat !== seat) {
+        if (seat > pt.seat) {
           newMessage = Message.beReady;
           return;
         } 
@@ -446,7 +498,7 @@ const App: React.FC = () => {
         return;
       }
       
-      if (pt.is_first) {
+      if (pt.awaiting_bid) {
           newMessage = Message.bet;
           return;
       } 
@@ -461,7 +513,7 @@ const App: React.FC = () => {
       buttonState: newButtonState
     });
     
-  }, [JSON.stringify(table)]);
+  }, [JSON.stringify(table), JSON.stringify(mySeat)]);
 
   const placeBet = async (amount: number) => {
     const seat = findMySeat(table);
@@ -574,8 +626,12 @@ const App: React.FC = () => {
       return -1;
     }
 
+    if(mySeat.seat !== -1) {
+      return mySeat.seat;
+    }
+
     for (let i: number = 0; i < 6; i++) {
-      if(t.players[i].address === client!.address) {
+      if(t.seats[i].player === client!.address) {
         return i;
       }
     }
@@ -620,7 +676,9 @@ const App: React.FC = () => {
       const scores = await getScores(client!, gameCodeHash, gameAddress);
       if(hasPlayerScore(scores.players[seat])) {
         const playerScore = scores.players[seat]!;
-        if(playerScore.address === client!.address) {
+        // Private tables blank the addresses of the scores, leaving the player's alias
+        const isMine = (playerScore.address === "") ? (playerScore.alias === mySeat.alias) : (playerScore.address === client!.address);
+        if(isMine) {
           if(playerScore.won) {
             winNotification(playerScore.score, scores.dealer.score, parseInt(playerScore.reward));
           } else {
@@ -632,7 +690,6 @@ const App: React.FC = () => {
   }
 
   const kickEvent = async (index: number) => {
-    const kickedAddress = table.players[index].address;
     loading();
 
     const tx = await client!.tx.compute.executeContract(
@@ -642,7 +699,6 @@ const App: React.FC = () => {
         codeHash: gameCodeHash,
         msg: {
           kick: {
-            target: kickedAddress,
             seat: index,
           },
         },
@@ -768,11 +824,11 @@ const App: React.FC = () => {
   }
 
   const getPlayerCards = (index: number) => {
-    if(!hasHand(table.players[index].hand)) {
+    if(!hasHand(table.seats[index].hand)) {
       return [];
     }
 
-    return toGameCards(table.players[index].hand!.cards);
+    return toGameCards(table.seats[index].hand!.cards);
   }
 
   const shortenAddress = (address: string) => {
@@ -783,12 +839,31 @@ const App: React.FC = () => {
     return address.slice(0,9) + "..." + address.slice(-3)
   }
 
+  // Private tables only show the seat aliases
+  const getSeatName = (index: number) => {
+    const seat = table.seats[index];
+    if(seat.player !== null) {
+      return shortenAddress(seat.player);
+    }
+
+    return seat.alias || "";
+  }
+
   const getPlayerScore = (index: number) => {
-    return getHandScore(table.players[index].hand);
+    return getHandScore(table.seats[index].hand);
+  }
+
+  // Only the dealer's up card is shown until the round is settled
+  const getDealerHand = (): Nullable<H> => {
+    if(table.dealer_up_card === null) {
+      return null;
+    }
+
+    return { cards: [{ ...table.dealer_up_card }], total_value: 0 };
   }
 
   const getDealerScore = () => {
-    return getHandScore(table.dealer_hand);
+    return getHandScore(getDealerHand());
   }
 
   const getHandScore = (hand: Nullable<H>) => {
@@ -814,11 +889,12 @@ const App: React.FC = () => {
   }
 
   const getDealerCards = () => {
-    if(!hasHand(table.dealer_hand)) {
+    const dealerHand = getDealerHand();
+    if(!hasHand(dealerHand)) {
       return [];
     }
 
-    return toGameCards(table.dealer_hand!.cards);
+    return toGameCards(dealerHand.cards);
   }
 
   const isPlayerRound = () => {
@@ -827,17 +903,16 @@ const App: React.FC = () => {
       return false;
     }
 
-    if(!isPlayerTurn(table.state)) {
+    if(!isPlayerTurn(table.turn)) {
       return false;
     }
 
-    const pt = (table.state as PT).PlayerTurn;
-    return (pt.player_seat === seat);
+    const pt = (table.turn as PT).player_turn;
+    return (pt.seat === seat);
   }
 
   const isPlayerFirstRound = () => {
-    const pt = (table.state as PT).PlayerTurn;
-    return isPlayerRound() && pt.is_first;
+    return isPlayerRound() && (table.turn as PT).player_turn.awaiting_bid;
   }
 
   return (
@@ -855,12 +930,12 @@ const App: React.FC = () => {
         lastScoreEvent={getLastScore}
       />
       <Hand title={`Dealer`} cards={getDealerCards()} isDealer={true} dealerScore={getDealerScore()} />
-      <Hands addresses={[shortenAddress(table.players[0].address),
-      shortenAddress(table.players[1].address),
-      shortenAddress(table.players[2].address),
-      shortenAddress(table.players[3].address),
-      shortenAddress(table.players[4].address),
-      shortenAddress(table.players[5].address),
+      <Hands addresses={[getSeatName(0),
+      getSeatName(1),
+      getSeatName(2),
+      getSeatName(3),
+      getSeatName(4),
+      getSeatName(5),
       ]}
       cardsArr= {[getPlayerCards(0), 
         getPlayerCards(1),